@@ -0,0 +1,210 @@
+use cairo_rs::{serde::deserialize_program::Location, types::relocatable::Relocatable};
+use pyo3::{exceptions::PyTypeError, prelude::*};
+use std::collections::HashSet;
+
+use crate::relocatable::PyRelocatable;
+
+const BREAKPOINT_TYPE_ERROR_MSG: &str =
+    "breakpoint must be a RelocatableValue, an int pc or a (filename, line) pair";
+
+/// A place where `run_until_breakpoint` should stop.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    Pc(Relocatable),
+    SourceLocation(String, u32),
+}
+
+impl Breakpoint {
+    /// Builds a breakpoint from the Python arguments of `add_breakpoint`/`remove_breakpoint`.
+    /// Integer pcs are taken as offsets in the program segment.
+    pub fn from_py(location: &PyAny, line: Option<u32>) -> PyResult<Breakpoint> {
+        match line {
+            Some(line) => Ok(Breakpoint::SourceLocation(
+                location
+                    .extract::<String>()
+                    .map_err(|_| PyTypeError::new_err(BREAKPOINT_TYPE_ERROR_MSG))?,
+                line,
+            )),
+            None => {
+                if let Ok(pc) = location.extract::<PyRelocatable>() {
+                    Ok(Breakpoint::Pc(Relocatable::from(&pc)))
+                } else if let Ok(offset) = location.extract::<usize>() {
+                    Ok(Breakpoint::Pc(Relocatable::from((0, offset))))
+                } else {
+                    Err(PyTypeError::new_err(BREAKPOINT_TYPE_ERROR_MSG))
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Breakpoints {
+    breakpoints: HashSet<Breakpoint>,
+    /// Source line of the last pc checked, as a line breakpoint only stops at the first
+    /// instruction run of the line.
+    last_line: Option<(String, u32)>,
+}
+
+impl Breakpoints {
+    pub fn add(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.insert(breakpoint);
+    }
+
+    pub fn remove(&mut self, breakpoint: &Breakpoint) -> bool {
+        self.breakpoints.remove(breakpoint)
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.last_line = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+
+    pub fn pcs(&self) -> Vec<Relocatable> {
+        self.breakpoints
+            .iter()
+            .filter_map(|breakpoint| match breakpoint {
+                Breakpoint::Pc(pc) => Some(*pc),
+                Breakpoint::SourceLocation(..) => None,
+            })
+            .collect()
+    }
+
+    /// Returns true if there is a breakpoint at `pc`, or at the source line `pc` belongs to when
+    /// the previous pc checked belongs to another line. `location` is only computed when a source
+    /// location breakpoint has been set.
+    pub fn hit(&mut self, pc: &Relocatable, location: impl FnOnce() -> Option<Location>) -> bool {
+        let pc_hit = self.breakpoints.contains(&Breakpoint::Pc(*pc));
+        if !self
+            .breakpoints
+            .iter()
+            .any(|breakpoint| matches!(breakpoint, Breakpoint::SourceLocation(..)))
+        {
+            return pc_hit;
+        }
+        let line = location().map(|location| (location.input_file.filename, location.start_line));
+        let new_line = line != self.last_line;
+        self.last_line = line;
+        let line_hit = match &self.last_line {
+            Some((filename, line)) => {
+                new_line
+                    && self
+                        .breakpoints
+                        .contains(&Breakpoint::SourceLocation(filename.clone(), *line))
+            }
+            None => false,
+        };
+        pc_hit || line_hit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cairo_rs::serde::deserialize_program::InputFile;
+
+    fn location(filename: &str, line: u32) -> Location {
+        Location {
+            end_line: line,
+            end_col: 10,
+            input_file: InputFile {
+                filename: filename.to_string(),
+            },
+            parent_location: None,
+            start_line: line,
+            start_col: 1,
+        }
+    }
+
+    #[test]
+    fn breakpoint_from_py() {
+        Python::with_gil(|py| {
+            assert_eq!(
+                Breakpoint::from_py(py.eval("3", None, None).unwrap(), None).unwrap(),
+                Breakpoint::Pc(Relocatable::from((0, 3)))
+            );
+            assert_eq!(
+                Breakpoint::from_py(PyRelocatable::from((1, 2)).into_py(py).as_ref(py), None)
+                    .unwrap(),
+                Breakpoint::Pc(Relocatable::from((1, 2)))
+            );
+            assert_eq!(
+                Breakpoint::from_py(py.eval("'main.cairo'", None, None).unwrap(), Some(7)).unwrap(),
+                Breakpoint::SourceLocation(String::from("main.cairo"), 7)
+            );
+            assert!(Breakpoint::from_py(py.eval("[]", None, None).unwrap(), None).is_err());
+            assert!(Breakpoint::from_py(py.eval("3", None, None).unwrap(), Some(7)).is_err());
+        });
+    }
+
+    #[test]
+    fn hit_pc_breakpoint() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.add(Breakpoint::Pc(Relocatable::from((0, 4))));
+
+        assert!(breakpoints.hit(&Relocatable::from((0, 4)), || None));
+        assert!(!breakpoints.hit(&Relocatable::from((0, 5)), || None));
+        assert_eq!(breakpoints.pcs(), vec![Relocatable::from((0, 4))]);
+
+        assert!(breakpoints.remove(&Breakpoint::Pc(Relocatable::from((0, 4)))));
+        assert!(breakpoints.is_empty());
+    }
+
+    #[test]
+    fn hit_source_location_breakpoint() {
+        let mut breakpoints = Breakpoints::default();
+        breakpoints.add(Breakpoint::SourceLocation(String::from("main.cairo"), 7));
+
+        assert!(
+            breakpoints.hit(&Relocatable::from((0, 0)), || Some(location(
+                "main.cairo",
+                7
+            )))
+        );
+        assert!(
+            !breakpoints.hit(&Relocatable::from((0, 0)), || Some(location(
+                "main.cairo",
+                8
+            )))
+        );
+        assert!(!breakpoints.hit(&Relocatable::from((0, 0)), || None));
+
+        // The other instructions of the line don't stop the run again, until it comes back to it
+        assert!(
+            breakpoints.hit(&Relocatable::from((0, 1)), || Some(location(
+                "main.cairo",
+                7
+            )))
+        );
+        assert!(
+            !breakpoints.hit(&Relocatable::from((0, 2)), || Some(location(
+                "main.cairo",
+                7
+            )))
+        );
+        assert!(
+            !breakpoints.hit(&Relocatable::from((0, 3)), || Some(location(
+                "main.cairo",
+                8
+            )))
+        );
+        assert!(
+            breakpoints.hit(&Relocatable::from((0, 1)), || Some(location(
+                "main.cairo",
+                7
+            )))
+        );
+
+        breakpoints.clear();
+        assert!(
+            !breakpoints.hit(&Relocatable::from((0, 0)), || Some(location(
+                "main.cairo",
+                7
+            )))
+        );
+    }
+}
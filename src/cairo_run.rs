@@ -1,6 +1,118 @@
+use crate::cairo_runner::{PyCairoRunner, PyExecutionResources};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+};
+use std::{collections::HashMap, sync::Mutex, thread};
+
+fn available_parallelism() -> usize {
+    thread::available_parallelism().map_or(1, usize::from)
+}
+
+/// Runs each of `programs` (compiled program JSONs) from `entrypoint` on its own runner, on at
+/// most `n_threads` threads (by default, one per available CPU) started for this call.
+/// `hint_locals[i]`, if given, holds the variables that the hints of `programs[i]` can read, such
+/// as `program_input`. Returns the output and execution resources of every run, in the order of
+/// `programs`, or the error of the first program that failed. Hints are still run, each thread
+/// taking the GIL only while running them.
+#[pyfunction]
+#[args(
+    hint_locals = "None",
+    entrypoint = "\"main\"",
+    layout = "\"plain\"",
+    proof_mode = "false",
+    n_threads = "None"
+)]
+pub fn run_many(
+    py: Python,
+    programs: Vec<String>,
+    hint_locals: Option<Vec<Option<HashMap<String, PyObject>>>>,
+    entrypoint: &str,
+    layout: &str,
+    proof_mode: bool,
+    n_threads: Option<usize>,
+) -> PyResult<Vec<(String, PyExecutionResources)>> {
+    let n_programs = programs.len();
+    let hint_locals = match hint_locals {
+        Some(hint_locals) if hint_locals.len() != n_programs => {
+            return Err(PyValueError::new_err(format!(
+                "Expected one entry of hint_locals per program ({n_programs}), got {}",
+                hint_locals.len()
+            )))
+        }
+        Some(hint_locals) => hint_locals,
+        None => (0..n_programs).map(|_| None).collect(),
+    };
+    let n_threads = n_threads
+        .unwrap_or_else(available_parallelism)
+        .clamp(1, n_programs.max(1));
+    let pending = Mutex::new(
+        programs
+            .into_iter()
+            .zip(hint_locals)
+            .enumerate()
+            .rev()
+            .collect::<Vec<_>>(),
+    );
+
+    // The threads only live for this call, so that nested or concurrent calls never wait on each
+    // other
+    let results = py.allow_threads(|| {
+        thread::scope(|scope| {
+            let workers = (0..n_threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut results = Vec::new();
+                        loop {
+                            let next = pending.lock().unwrap().pop();
+                            let (index, (program, hint_locals)) = match next {
+                                Some(next) => next,
+                                None => break,
+                            };
+                            let result =
+                                run_program(program, hint_locals, entrypoint, layout, proof_mode);
+                            results.push((index, result));
+                        }
+                        results
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| worker.join())
+                .collect::<Result<Vec<_>, _>>()
+        })
+    });
+
+    let mut results = results
+        .map_err(|_| PyRuntimeError::new_err("A thread panicked while running a program"))?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+fn run_program(
+    program: String,
+    hint_locals: Option<HashMap<String, PyObject>>,
+    entrypoint: &str,
+    layout: &str,
+    proof_mode: bool,
+) -> PyResult<(String, PyExecutionResources)> {
+    let mut runner = PyCairoRunner::new(
+        program,
+        Some(entrypoint.to_string()),
+        Some(layout.to_string()),
+        proof_mode,
+    )?;
+    runner.cairo_run_py(false, None, None, hint_locals, None, None, None)?;
+    Ok((runner.get_output()?, runner.get_execution_resources()?))
+}
+
 #[cfg(test)]
 mod test {
-    use crate::cairo_runner::PyCairoRunner;
+    use super::*;
     use std::fs;
 
     #[test]
@@ -10,7 +122,7 @@ mod test {
         let mut runner =
             PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .expect("Couldn't run program");
     }
 
@@ -26,7 +138,7 @@ mod test {
         )
         .unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .expect("Couldn't run program");
     }
 
@@ -37,7 +149,92 @@ mod test {
         let mut runner =
             PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .expect("Couldn't run program");
     }
+
+    #[test]
+    fn run_many_programs() {
+        let fibonacci = fs::read_to_string("cairo_programs/fibonacci.json").unwrap();
+        let array_sum = fs::read_to_string("cairo_programs/array_sum.json").unwrap();
+        let mut runner =
+            PyCairoRunner::new(fibonacci.clone(), Some("main".to_string()), None, false).unwrap();
+        runner
+            .cairo_run_py(false, None, None, None, None, None, None)
+            .unwrap();
+        let fibonacci_steps = runner.get_execution_resources().unwrap().n_steps();
+
+        Python::with_gil(|py| {
+            let results = run_many(
+                py,
+                vec![fibonacci.clone(), array_sum, fibonacci],
+                None,
+                "main",
+                "all",
+                false,
+                Some(2),
+            )
+            .unwrap();
+
+            assert_eq!(results.len(), 3);
+            assert_eq!(results[0].1.n_steps(), fibonacci_steps);
+            assert_ne!(results[1].1.n_steps(), fibonacci_steps);
+            assert_eq!(results[2].1.n_steps(), fibonacci_steps);
+        });
+    }
+
+    #[test]
+    fn run_many_reports_errors() {
+        let fibonacci = fs::read_to_string("cairo_programs/fibonacci.json").unwrap();
+        Python::with_gil(|py| {
+            assert!(run_many(
+                py,
+                vec![fibonacci, String::from("{}")],
+                None,
+                "main",
+                "plain",
+                false,
+                None
+            )
+            .is_err());
+            assert!(run_many(py, vec![], None, "main", "plain", false, None)
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn run_many_with_hint_locals() {
+        let find_element = fs::read_to_string("cairo_programs/find_element.json").unwrap();
+        Python::with_gil(|py| {
+            let max_size = |size: usize| {
+                Some(HashMap::from([(
+                    String::from("__find_element_max_size"),
+                    size.to_object(py),
+                )]))
+            };
+            let programs = vec![find_element.clone(), find_element];
+            assert!(run_many(
+                py,
+                programs.clone(),
+                Some(vec![max_size(100), None]),
+                "main",
+                "all",
+                false,
+                None
+            )
+            .is_ok());
+            assert!(run_many(
+                py,
+                programs.clone(),
+                Some(vec![None, max_size(1)]),
+                "main",
+                "all",
+                false,
+                None
+            )
+            .is_err());
+            assert!(run_many(py, programs, Some(vec![None]), "main", "all", false, None).is_err());
+        });
+    }
 }
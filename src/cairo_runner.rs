@@ -1,10 +1,22 @@
 use crate::{
+    breakpoints::{Breakpoint, Breakpoints},
+    cancellation::{PyCancelToken, RunCancelledError, SIGNALS_CHECK_INTERVAL},
+    exec_scopes::PyExecutionScopes,
+    identifier_manager::IdentifierManager,
     instruction_location::InstructionLocation,
-    memory::PyMemory,
+    memory::{check_not_frozen, segment_sizes, write_memory, FrozenMemoryError, PyMemory},
     memory_segments::PySegmentManager,
     relocatable::{PyMaybeRelocatable, PyRelocatable},
+    relocation::{memory_to_bytes, trace_to_bytes, PyRelocatedTraceEntry},
+    run_context::PyRunContext,
+    run_resources::{PyRunResources, ResourcesError},
+    sandbox::{HintSandboxError, Sandbox},
+    scope_manager::{copy_exec_scopes, deepcopy},
+    snapshot::PyRunnerSnapshot,
+    strict_mode::{HintAllowlist, UnauthorizedHint},
     utils::to_py_error,
     vm_core::PyVM,
+    watchpoints::Watchpoints,
 };
 use cairo_rs::{
     bigint,
@@ -12,6 +24,7 @@ use cairo_rs::{
     hint_processor::builtin_hint_processor::builtin_hint_processor_definition::BuiltinHintProcessor,
     serde::deserialize_program::Member,
     types::{
+        exec_scope::ExecutionScopes,
         program::Program,
         relocatable::{MaybeRelocatable, Relocatable},
     },
@@ -24,20 +37,36 @@ use cairo_rs::{
         },
         runners::cairo_runner::{CairoRunner, ExecutionResources},
         security::verify_secure_runner,
+        trace::trace_entry::RelocatedTraceEntry,
+        vm_core::VirtualMachine,
     },
 };
 use num_bigint::BigInt;
 use pyo3::{
-    exceptions::{PyNotImplementedError, PyTypeError, PyValueError},
+    exceptions::{PyException, PyTypeError, PyValueError},
     prelude::*,
-    types::PyIterator,
+    types::{PyBytes, PyIterator},
+};
+use std::{
+    any::Any,
+    borrow::BorrowMut,
+    collections::{HashMap, HashSet},
+    iter::zip,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    rc::Rc,
+    time::Instant,
 };
-use std::{any::Any, borrow::BorrowMut, collections::HashMap, iter::zip, path::PathBuf, rc::Rc};
-
-pyo3::import_exception!(starkware.cairo.lang.vm.utils, ResourcesError);
 
 const MEMORY_GET_SEGMENT_USED_SIZE_MSG: &str = "Failed to segment used size";
 const FAILED_TO_GET_INITIAL_FP: &str = "Failed to get initial segment";
+const ACCESSED_ADDRESSES_NOT_TRACKED_MSG: &str = "The VM doesn't track the accessed addresses";
+const MEMORY_NOT_RELOCATED_MSG: &str = "The memory isn't relocated yet, call relocate() first";
+const NO_BREAKPOINTS_NOR_END_MSG: &str =
+    "run_until_breakpoint needs either a breakpoint or the end of the run to stop at";
+
+type HintDataDictionary = HashMap<usize, Vec<Box<dyn Any>>>;
 
 #[pyclass(unsendable)]
 #[pyo3(name = "CairoRunner")]
@@ -48,6 +77,15 @@ pub struct PyCairoRunner {
     hint_locals: HashMap<String, PyObject>,
     struct_types: Rc<HashMap<String, HashMap<String, Member>>>,
     static_locals: Option<HashMap<String, PyObject>>,
+    identifiers: IdentifierManager,
+    hint_data_dictionary: Option<Rc<HintDataDictionary>>,
+    breakpoints: Breakpoints,
+    watchpoints: Watchpoints,
+    end: Option<Relocatable>,
+    cancel_token: PyCancelToken,
+    steps_since_signals_check: usize,
+    /// Whether the last step failed because a watchpoint callback raised.
+    watchpoint_failed: bool,
 }
 
 #[pymethods]
@@ -59,6 +97,7 @@ impl PyCairoRunner {
         layout: Option<String>,
         proof_mode: bool,
     ) -> PyResult<Self> {
+        let identifiers = IdentifierManager::from_program_json(&program)?;
         let program =
             Program::from_reader(program.as_bytes(), entrypoint.as_deref()).map_err(to_py_error)?;
         let cairo_runner = CairoRunner::new(
@@ -84,6 +123,14 @@ impl PyCairoRunner {
             hint_locals: HashMap::new(),
             struct_types: Rc::new(struct_types),
             static_locals: None,
+            identifiers,
+            hint_data_dictionary: None,
+            breakpoints: Breakpoints::default(),
+            watchpoints: Watchpoints::default(),
+            end: None,
+            cancel_token: PyCancelToken::default(),
+            steps_since_signals_check: 0,
+            watchpoint_failed: false,
         })
     }
 
@@ -96,6 +143,7 @@ impl PyCairoRunner {
         hint_locals: Option<HashMap<String, PyObject>>,
         static_locals: Option<HashMap<String, PyObject>>,
         entrypoint: Option<&str>,
+        trace_enabled: Option<bool>,
     ) -> PyResult<()> {
         if let Some(entrypoint) = entrypoint {
             self.inner
@@ -112,7 +160,7 @@ impl PyCairoRunner {
 
         self.static_locals = static_locals;
 
-        if trace_file.is_none() {
+        if trace_file.is_none() && !trace_enabled.unwrap_or(false) {
             (*self.pyvm.vm).borrow_mut().disable_trace();
         }
         if let Err(error) = self.run_until_pc(&end, None) {
@@ -141,12 +189,7 @@ impl PyCairoRunner {
 
         if let Some(trace_path) = trace_file {
             let trace_path = PathBuf::from(trace_path);
-            let relocated_trace = self
-                .inner
-                .relocated_trace
-                .as_ref()
-                .ok_or(CairoRunError::Trace(TraceError::TraceNotEnabled))
-                .map_err(to_py_error)?;
+            let relocated_trace = self.get_relocated_trace()?;
 
             match cairo_rs::cairo_run::write_binary_trace(relocated_trace, &trace_path) {
                 Ok(()) => (),
@@ -166,54 +209,245 @@ impl PyCairoRunner {
     }
 
     pub fn initialize(&mut self) -> PyResult<PyRelocatable> {
-        self.inner
+        check_not_frozen(&self.pyvm.memory_frozen)?;
+        let end = self
+            .inner
             .initialize(&mut (*self.pyvm.vm).borrow_mut())
-            .map(PyRelocatable::from)
-            .map_err(to_py_error)
+            .map_err(to_py_error)?;
+        self.end = Some(end);
+        Ok(end.into())
     }
 
-    pub fn initialize_segments(&mut self) {
+    pub fn initialize_segments(&mut self) -> PyResult<()> {
+        check_not_frozen(&self.pyvm.memory_frozen)?;
         self.inner
-            .initialize_segments(&mut (*self.pyvm.vm).borrow_mut(), None)
+            .initialize_segments(&mut (*self.pyvm.vm).borrow_mut(), None);
+        Ok(())
     }
 
+    /// Runs until `address` is reached. `run_resources` may be a `RunResources`, cairo-lang's
+    /// `RunResources` or a number of steps; the resources consumed by the run are reported back
+    /// to it, even if a limit was exceeded.
     pub fn run_until_pc(
         &mut self,
         address: &PyRelocatable,
-        run_resources_n_steps: Option<usize>,
+        run_resources: Option<&PyAny>,
     ) -> PyResult<()> {
-        let references = self.inner.get_reference_list();
-        let hint_data_dictionary = self
-            .inner
-            .get_hint_data_dictionary(&references, &mut self.hint_processor)
-            .map_err(to_py_error)?;
+        let mut resources = PyRunResources::from_py(run_resources)?;
+        let result = self.run_until_pc_with_resources(address.into(), &mut resources);
+        resources.update_py(run_resources)?;
+        result
+    }
+
+    /// Executes a single instruction (and its hints), returning the new run context.
+    pub fn step(&mut self) -> PyResult<PyRunContext> {
+        self.step_n(1)
+    }
 
-        let address = Into::<Relocatable>::into(address);
+    /// Executes `n` instructions, stopping early if the end of the run is reached.
+    pub fn step_n(&mut self, n: usize) -> PyResult<PyRunContext> {
+        let hint_data_dictionary = self.get_hint_data_dictionary()?;
         let constants = self.inner.get_constants().clone();
-        let mut steps_left = run_resources_n_steps.unwrap_or(1); // default value
-        while self.pyvm.vm.borrow().get_pc() != &address && steps_left > 0 {
-            self.pyvm.step(
-                &mut self.hint_processor,
-                &mut self.hint_locals,
-                &mut self.inner.exec_scopes,
-                &hint_data_dictionary,
-                Rc::clone(&self.struct_types),
-                &constants,
-                self.static_locals.as_ref(),
-            )?;
-            // Consume step
-            if run_resources_n_steps.is_some() {
-                steps_left -= 1;
+        for _ in 0..n {
+            if self.end.as_ref() == Some(self.pyvm.vm.borrow().get_pc()) {
+                break;
+            }
+            if let Err(error) = self.step_instruction(&hint_data_dictionary, &constants) {
+                return Err(self.as_vm_exception(error));
             }
         }
-        if self.pyvm.vm.borrow().get_pc() != &address {
-            return Err(ResourcesError::new_err(PyValueError::new_err(
-                "Error: Execution reached the end of the program.",
+        Ok(self.pyvm.run_context())
+    }
+
+    /// Runs until a breakpoint or `end` (by default, the end of the run set up by `initialize`
+    /// or `run_from_entrypoint`) is reached. Breakpoints are checked after each instruction, so
+    /// calling this method again resumes from the breakpoint the run stopped at. A source line
+    /// breakpoint stops the run when it gets to the line, not at each instruction of it.
+    pub fn run_until_breakpoint(&mut self, end: Option<PyRelocatable>) -> PyResult<PyRunContext> {
+        let end = end.map(|end| Relocatable::from(&end)).or(self.end);
+        if end.is_none() && self.breakpoints.is_empty() {
+            return Err(PyValueError::new_err(NO_BREAKPOINTS_NOR_END_MSG));
+        }
+
+        let hint_data_dictionary = self.get_hint_data_dictionary()?;
+        let constants = self.inner.get_constants().clone();
+        loop {
+            let pc = *self.pyvm.vm.borrow().get_pc();
+            if end == Some(pc) {
+                break;
+            }
+            if let Err(error) = self.step_instruction(&hint_data_dictionary, &constants) {
+                return Err(self.as_vm_exception(error));
+            }
+            let pc = *self.pyvm.vm.borrow().get_pc();
+            let hit = self.breakpoints.hit(&pc, || match pc.segment_index {
+                0 => get_location(pc.offset, &self.inner, None),
+                _ => None,
+            });
+            if hit {
+                break;
+            }
+        }
+        Ok(self.pyvm.run_context())
+    }
+
+    /// Runs `func(vm, ids, memory, scopes)` instead of the hints whose code is exactly `code`,
+    /// including the ones implemented in Rust. `scopes` is a dict with the variables of the
+    /// current scope.
+    pub fn register_hint(&mut self, code: String, func: &PyAny) -> PyResult<()> {
+        if !func.is_callable() {
+            return Err(PyTypeError::new_err(format!(
+                "Expected a callable for hint {code:?}, found {}.",
+                func.get_type().name()?
             )));
         }
+        self.pyvm.registered_hints.insert(code, func.into());
         Ok(())
     }
 
+    /// Only lets the hints in `allowed_hints`, given by their code or the SHA-256 hex digest of
+    /// it, run as Python code. Any other hint that isn't implemented in Rust nor registered with
+    /// `register_hint` makes the run fail with `UnauthorizedHint`.
+    pub fn enable_strict_mode(&mut self, allowed_hints: Vec<String>) {
+        self.pyvm.hint_allowlist = Some(HintAllowlist::new(allowed_hints));
+    }
+
+    pub fn disable_strict_mode(&mut self) {
+        self.pyvm.hint_allowlist = None;
+    }
+
+    #[getter]
+    pub fn strict_mode(&self) -> bool {
+        self.pyvm.hint_allowlist.is_some()
+    }
+
+    /// Runs the Python hints with a curated set of builtins, only letting them import
+    /// `allowed_modules` and their submodules (by default, `math` and the modules the hints of
+    /// the Cairo common library use) and set attributes of `ids`. Hints trying to do anything
+    /// else fail with `HintSandboxError`.
+    #[args(allowed_modules = "None")]
+    pub fn enable_sandbox(&mut self, allowed_modules: Option<Vec<String>>) {
+        self.pyvm.set_sandbox(Some(Sandbox::new(allowed_modules)));
+    }
+
+    pub fn disable_sandbox(&mut self) {
+        self.pyvm.set_sandbox(None);
+    }
+
+    #[getter]
+    pub fn sandboxed(&self) -> bool {
+        self.pyvm.is_sandboxed()
+    }
+
+    /// Adds a breakpoint either at a pc (a RelocatableValue, or an int offset in the program
+    /// segment), or at a Cairo source location given as `filename, line`.
+    #[args(line = "None")]
+    pub fn add_breakpoint(&mut self, location: &PyAny, line: Option<u32>) -> PyResult<()> {
+        self.breakpoints.add(Breakpoint::from_py(location, line)?);
+        Ok(())
+    }
+
+    /// Removes a breakpoint, returning whether it was set.
+    #[args(line = "None")]
+    pub fn remove_breakpoint(&mut self, location: &PyAny, line: Option<u32>) -> PyResult<bool> {
+        Ok(self
+            .breakpoints
+            .remove(&Breakpoint::from_py(location, line)?))
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear()
+    }
+
+    #[getter]
+    pub fn breakpoint_pcs(&self) -> Vec<PyRelocatable> {
+        self.breakpoints
+            .pcs()
+            .into_iter()
+            .map(PyRelocatable::from)
+            .collect()
+    }
+
+    /// Calls `callback(access)` with a `MemoryAccess` each time a cell of `memory` is written, or
+    /// also read when `reads` is true. `memory` is either a RelocatableValue or a
+    /// `(start, size)` pair. Reads are only reported for instructions, not hints. An exception
+    /// raised by the callback stops the run and is raised as is. Returns the id to pass to
+    /// `unwatch`.
+    #[args(reads = "false")]
+    pub fn watch(&mut self, memory: &PyAny, callback: &PyAny, reads: bool) -> PyResult<usize> {
+        if !callback.is_callable() {
+            return Err(PyTypeError::new_err(format!(
+                "Expected a callable for the watchpoint, found {}.",
+                callback.get_type().name()?
+            )));
+        }
+        self.watchpoints
+            .add(&self.pyvm.vm.borrow(), memory, callback.into(), reads)
+    }
+
+    /// Removes a watchpoint, returning whether it was set.
+    pub fn unwatch(&mut self, id: usize) -> bool {
+        self.watchpoints.remove(id)
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear()
+    }
+
+    /// Starts logging the memory accesses of the instructions and the writes of the hints,
+    /// dropping the previous log. It is queried with `memory.access_log()`. Hint writes are those
+    /// to their ids, to watched cells and past the end of a segment.
+    pub fn enable_access_log(&self) {
+        *(*self.pyvm.access_log).borrow_mut() = Some(Vec::new());
+    }
+
+    pub fn disable_access_log(&self) {
+        *(*self.pyvm.access_log).borrow_mut() = None;
+    }
+
+    /// Captures the current state of the run: memory, registers, builtin runners, execution
+    /// scopes and hint locals.
+    pub fn snapshot(&self, py: Python) -> PyResult<PyRunnerSnapshot> {
+        Ok(PyRunnerSnapshot {
+            vm: self.pyvm.vm.borrow().clone(),
+            exec_scopes: copy_exec_scopes(py, &self.inner.exec_scopes)?,
+            hint_locals: self
+                .hint_locals
+                .iter()
+                .map(|(name, pyobj)| (name.clone(), deepcopy(py, pyobj)))
+                .collect(),
+            end: self.end,
+        })
+    }
+
+    /// Rolls the run back to the state captured by `snapshot`.
+    /// Memory objects previously obtained from this runner see the restored state.
+    pub fn restore(&mut self, py: Python, snapshot: &PyRunnerSnapshot) -> PyResult<()> {
+        check_not_frozen(&self.pyvm.memory_frozen)?;
+        self.inner.exec_scopes = copy_exec_scopes(py, &snapshot.exec_scopes)?;
+        self.hint_locals = snapshot
+            .hint_locals
+            .iter()
+            .map(|(name, pyobj)| (name.clone(), deepcopy(py, pyobj)))
+            .collect();
+        *(*self.pyvm.vm).borrow_mut() = snapshot.vm.clone();
+        self.pyvm.failed_hint_index = None;
+        self.end = snapshot.end;
+        Ok(())
+    }
+
+    /// A live view of the execution scopes of the hints.
+    #[getter]
+    pub fn exec_scopes(slf: PyRef<Self>) -> PyExecutionScopes {
+        PyExecutionScopes::new(slf.into())
+    }
+
+    /// The token that cancels the runs of this runner. It may be cancelled from any thread.
+    #[getter]
+    pub fn cancel_token(&self) -> PyCancelToken {
+        self.cancel_token.clone()
+    }
+
     pub fn mark_as_accessed(&mut self, address: PyRelocatable, size: usize) -> PyResult<()> {
         (*self.pyvm.vm)
             .borrow_mut()
@@ -221,12 +455,67 @@ impl PyCairoRunner {
             .map_err(to_py_error)
     }
 
+    /// Returns the sorted addresses accessed by the instructions run or marked with
+    /// `mark_as_accessed`, in the segment `segment_index` or in all of them. Cells with a value
+    /// that are missing from it are the memory holes.
+    #[args(segment_index = "None")]
+    pub fn get_accessed_addresses(
+        &self,
+        segment_index: Option<isize>,
+    ) -> PyResult<Vec<PyRelocatable>> {
+        let vm = self.pyvm.vm.borrow();
+        let mut addresses = vm
+            .accessed_addresses
+            .as_ref()
+            .ok_or_else(|| PyValueError::new_err(ACCESSED_ADDRESSES_NOT_TRACKED_MSG))?
+            .iter()
+            .filter(|address| segment_index.map_or(true, |index| address.segment_index == index))
+            .map(|address| (address.segment_index, address.offset))
+            .collect::<Vec<_>>();
+        addresses.sort_unstable();
+        addresses.dedup();
+        Ok(addresses.into_iter().map(PyRelocatable::from).collect())
+    }
+
     pub fn relocate(&mut self) -> PyResult<()> {
         self.inner
             .relocate(&mut (*self.pyvm.vm).borrow_mut())
             .map_err(to_py_error)
     }
 
+    /// The values of the relocated memory by address, as written by `cairo_run` to `memory_file`.
+    /// Available once the memory is relocated.
+    #[getter]
+    pub fn relocated_memory(&self) -> PyResult<HashMap<usize, BigInt>> {
+        Ok(self
+            .get_relocated_memory()?
+            .iter()
+            .enumerate()
+            .filter_map(|(address, value)| Some((address, value.clone()?)))
+            .collect())
+    }
+
+    /// The relocated memory in the binary format of `memory_file`.
+    pub fn relocated_memory_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        memory_to_bytes(py, self.get_relocated_memory()?)
+    }
+
+    /// The relocated trace, as written by `cairo_run` to `trace_file`. `cairo_run` only keeps the
+    /// trace when given a `trace_file` or `trace_enabled=True`.
+    #[getter]
+    pub fn relocated_trace(&self) -> PyResult<Vec<PyRelocatedTraceEntry>> {
+        Ok(self
+            .get_relocated_trace()?
+            .iter()
+            .map(PyRelocatedTraceEntry::from)
+            .collect())
+    }
+
+    /// The relocated trace in the binary format of `trace_file`.
+    pub fn relocated_trace_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        trace_to_bytes(py, self.get_relocated_trace()?)
+    }
+
     pub fn get_output(&mut self) -> PyResult<String> {
         self.inner
             .get_output(&mut (*self.pyvm.vm).borrow_mut())
@@ -362,8 +651,9 @@ impl PyCairoRunner {
         static_locals: Option<HashMap<String, PyObject>>,
         typed_args: Option<bool>,
         verify_secure: Option<bool>,
-        run_resources: Option<PyRunResources>,
+        run_resources: Option<&PyAny>,
         apply_modulo_to_args: Option<bool>,
+        use_full_name: Option<bool>,
     ) -> PyResult<()> {
         enum Either {
             MaybeRelocatable(MaybeRelocatable),
@@ -388,8 +678,9 @@ impl PyCairoRunner {
 
         let entrypoint = if let Ok(x) = entrypoint.extract::<usize>() {
             x
-        } else if entrypoint.extract::<String>().is_ok() {
-            return Err(PyNotImplementedError::new_err(()));
+        } else if let Ok(name) = entrypoint.extract::<String>() {
+            self.identifiers
+                .get_label(&name, use_full_name.unwrap_or(false))?
         } else {
             return Err(PyTypeError::new_err("entrypoint must be int or str"));
         };
@@ -455,15 +746,13 @@ impl PyCairoRunner {
                 return_fp,
             )
             .map_err(to_py_error)?;
+        self.end = Some(end);
 
         self.inner
             .initialize_vm(&mut (*self.pyvm.vm).borrow_mut())
             .map_err(to_py_error)?;
 
-        if let Err(error) = self.run_until_pc(
-            &end.into(),
-            run_resources.and_then(|resource| resource.n_steps),
-        ) {
+        if let Err(error) = self.run_until_pc(&end.into(), run_resources) {
             return Err(self.as_vm_exception(error));
         }
 
@@ -486,14 +775,17 @@ impl PyCairoRunner {
 
     /// Inserts a value into a memory address given by a Relocatable value.
     pub fn insert(&self, key: &PyRelocatable, value: PyMaybeRelocatable) -> PyResult<()> {
-        (*self.pyvm.vm)
-            .borrow_mut()
-            .insert_value(&key.into(), value)
-            .map_err(to_py_error)
+        check_not_frozen(&self.pyvm.memory_frozen)?;
+        write_memory(|| {
+            (*self.pyvm.vm)
+                .borrow_mut()
+                .insert_value(&key.into(), value)
+        })
     }
 
     // Initialize all the builtins and segments.
     pub fn initialize_function_runner(&mut self) -> PyResult<()> {
+        check_not_frozen(&self.pyvm.memory_frozen)?;
         self.inner
             .initialize_function_runner(&mut (*self.pyvm.vm).borrow_mut())
             .map_err(to_py_error)
@@ -505,6 +797,7 @@ impl PyCairoRunner {
         arg: Py<PyAny>,
         apply_modulo_to_args: bool,
     ) -> PyResult<PyObject> {
+        check_not_frozen(&self.pyvm.memory_frozen)?;
         Ok(
             PyMaybeRelocatable::from(match PyIterator::from_object(py, &arg) {
                 Ok(iterator) => {
@@ -542,6 +835,7 @@ impl PyCairoRunner {
         apply_modulo_to_args: bool,
     ) -> PyResult<PyObject> {
         let ptr: MaybeRelocatable = ptr.into();
+        check_not_frozen(&self.pyvm.memory_frozen)?;
 
         let arg_iter = PyIterator::from_object(py, &arg)?;
         let mut data = Vec::new();
@@ -553,11 +847,8 @@ impl PyCairoRunner {
             );
         }
 
-        (*self.pyvm.vm)
-            .borrow_mut()
-            .load_data(&ptr, data)
+        write_memory(|| (*self.pyvm.vm).borrow_mut().load_data(&ptr, data))
             .map(|x| PyMaybeRelocatable::from(x).to_object(py))
-            .map_err(to_py_error)
     }
 
     /// Return a value from memory given its address.
@@ -652,8 +943,225 @@ impl PyCairoRunner {
 
 pyo3::import_exception!(starkware.cairo.lang.vm.vm_exceptions, VmException);
 
+/// A VM taken out of its runner to run instructions with the GIL released.
+struct DetachedVm(VirtualMachine);
+
+// SAFETY: `Python::allow_threads` runs the closure on the current thread, and owning the VM
+// means that no handle to it can be used by the Python threads that run meanwhile. The VM isn't
+// `Send` because of its validation rules, and the only ones holding Python objects are those added
+// from Python, with which runs don't detach the VM.
+unsafe impl Send for DetachedVm {}
+
 impl PyCairoRunner {
+    pub(crate) fn exec_scopes_ref(&self) -> &ExecutionScopes {
+        &self.inner.exec_scopes
+    }
+
+    pub(crate) fn exec_scopes_mut(&mut self) -> &mut ExecutionScopes {
+        &mut self.inner.exec_scopes
+    }
+
+    /// Returns the hints of the program indexed by pc, compiling them on first use.
+    fn get_hint_data_dictionary(&mut self) -> PyResult<Rc<HintDataDictionary>> {
+        if let Some(hint_data_dictionary) = &self.hint_data_dictionary {
+            return Ok(Rc::clone(hint_data_dictionary));
+        }
+        let references = self.inner.get_reference_list();
+        let hint_data_dictionary = Rc::new(
+            self.inner
+                .get_hint_data_dictionary(&references, &mut self.hint_processor)
+                .map_err(to_py_error)?,
+        );
+        self.hint_data_dictionary = Some(Rc::clone(&hint_data_dictionary));
+        Ok(hint_data_dictionary)
+    }
+
+    fn run_until_pc_with_resources(
+        &mut self,
+        address: Relocatable,
+        resources: &mut PyRunResources,
+    ) -> PyResult<()> {
+        let result = self.run_steps_until_pc(address, resources);
+        // Without a memory limit, the memory is only measured once the run stops
+        resources.consumed_memory_cells = segment_sizes(&self.pyvm.vm.borrow()).iter().sum();
+        result
+    }
+
+    fn run_steps_until_pc(
+        &mut self,
+        address: Relocatable,
+        resources: &mut PyRunResources,
+    ) -> PyResult<()> {
+        let hint_data_dictionary = self.get_hint_data_dictionary()?;
+        let constants = self.inner.get_constants().clone();
+        let hint_pcs = hint_data_dictionary.keys().copied().collect();
+        let start = Instant::now();
+        let time_before_run = resources.consumed_time;
+        while self.pyvm.vm.borrow().get_pc() != &address {
+            check_not_frozen(&self.pyvm.memory_frozen)?;
+            let pc_offset = self.pyvm.vm.borrow().get_pc().offset;
+            // Validation rules added from Python run Python code on writes, and read the memory
+            // of the runner's VM, so they need it to stay in place
+            if !hint_data_dictionary.contains_key(&pc_offset)
+                && !self.is_watching_memory()
+                && !self.pyvm.has_py_validation_rules.get()
+            {
+                self.run_hint_free_instructions(
+                    address,
+                    &hint_pcs,
+                    resources,
+                    start,
+                    time_before_run,
+                )?;
+                continue;
+            }
+            resources.check_before_step()?;
+            let py_hints_run = self.step_instruction(&hint_data_dictionary, &constants)?;
+            let memory_cells = resources
+                .limits_memory()
+                .then(|| segment_sizes(&self.pyvm.vm.borrow()).iter().sum());
+            resources.consume_step(
+                py_hints_run,
+                memory_cells,
+                time_before_run + start.elapsed().as_secs_f64(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Executes instructions until reaching `address`, an instruction with hints, or
+    /// `SIGNALS_CHECK_INTERVAL` steps. As no Python code can run meanwhile, the GIL is released
+    /// so that other Python threads can make progress. The VM is taken out of the runner for the
+    /// duration, so the `memory` and `vm` objects handed to Python see an empty VM until then.
+    fn run_hint_free_instructions(
+        &mut self,
+        address: Relocatable,
+        hint_pcs: &HashSet<usize>,
+        resources: &mut PyRunResources,
+        start: Instant,
+        time_before_run: f64,
+    ) -> PyResult<()> {
+        let placeholder =
+            VirtualMachine::new(self.pyvm.vm.borrow().get_prime().clone(), false, Vec::new());
+        let vm = DetachedVm(mem::replace(
+            &mut *(*self.pyvm.vm).borrow_mut(),
+            placeholder,
+        ));
+        let cancel_token = self.cancel_token.clone();
+
+        let (vm, result) = Python::with_gil(|py| {
+            py.allow_threads(move || {
+                let mut vm = vm;
+                // A panic is resumed once the VM is back in the runner, so that it isn't lost
+                let result = panic::catch_unwind(AssertUnwindSafe(|| -> PyResult<()> {
+                    for _ in 0..SIGNALS_CHECK_INTERVAL {
+                        let pc = *vm.0.get_pc();
+                        if pc == address || hint_pcs.contains(&pc.offset) {
+                            break;
+                        }
+                        cancel_token.check()?;
+                        resources.check_before_step()?;
+                        write_memory(|| vm.0.step_instruction())?;
+                        let memory_cells = resources
+                            .limits_memory()
+                            .then(|| segment_sizes(&vm.0).iter().sum());
+                        resources.consume_step(
+                            0,
+                            memory_cells,
+                            time_before_run + start.elapsed().as_secs_f64(),
+                        )?;
+                    }
+                    Ok(())
+                }));
+                (vm, result)
+            })
+        });
+        *(*self.pyvm.vm).borrow_mut() = vm.0;
+        result.unwrap_or_else(|panic| panic::resume_unwind(panic))?;
+        Python::with_gil(|py| py.check_signals())
+    }
+
+    /// Executes one instruction, returning how many hints were run as Python code.
+    /// Before that, the run is stopped if it was cancelled or if a Python signal handler
+    /// (e.g. the one raising `KeyboardInterrupt`) failed, leaving the VM at the current step.
+    fn step_instruction(
+        &mut self,
+        hint_data_dictionary: &HintDataDictionary,
+        constants: &HashMap<String, BigInt>,
+    ) -> PyResult<usize> {
+        self.watchpoint_failed = false;
+        check_not_frozen(&self.pyvm.memory_frozen)?;
+        self.cancel_token.check()?;
+        self.steps_since_signals_check += 1;
+        if self.steps_since_signals_check >= SIGNALS_CHECK_INTERVAL {
+            self.steps_since_signals_check = 0;
+            Python::with_gil(|py| py.check_signals())?;
+        }
+        let mut before_step = self.is_watching_memory().then(|| {
+            let hint_cells = self
+                .pyvm
+                .hint_ids_cells(hint_data_dictionary, &self.struct_types);
+            self.watchpoints
+                .before_step(&self.pyvm.vm.borrow(), hint_cells)
+        });
+        let py_hints_run = self.pyvm.run_hints(
+            &mut self.hint_processor,
+            &mut self.hint_locals,
+            &mut self.inner.exec_scopes,
+            hint_data_dictionary,
+            Rc::clone(&self.struct_types),
+            constants,
+            self.static_locals.as_ref(),
+        )?;
+        if let Some(before_step) = before_step.as_mut() {
+            before_step.after_hints(&self.pyvm.vm.borrow());
+        }
+        self.pyvm.run_instruction()?;
+        if let Some(before_step) = before_step {
+            let result =
+                self.watchpoints
+                    .after_step(&self.pyvm.vm, before_step, &self.pyvm.access_log);
+            self.watchpoint_failed = result.is_err();
+            result?;
+        }
+        Ok(py_hints_run)
+    }
+
+    fn get_relocated_memory(&self) -> PyResult<&Vec<Option<BigInt>>> {
+        if self.inner.relocated_memory.is_empty() {
+            return Err(PyValueError::new_err(MEMORY_NOT_RELOCATED_MSG));
+        }
+        Ok(&self.inner.relocated_memory)
+    }
+
+    fn get_relocated_trace(&self) -> PyResult<&Vec<RelocatedTraceEntry>> {
+        self.inner
+            .relocated_trace
+            .as_ref()
+            .ok_or(CairoRunError::Trace(TraceError::TraceNotEnabled))
+            .map_err(to_py_error)
+    }
+
+    fn is_watching_memory(&self) -> bool {
+        !self.watchpoints.is_empty() || self.pyvm.access_log.borrow().is_some()
+    }
+
     fn as_vm_exception(&self, error: PyErr) -> PyErr {
+        // Running out of resources, cancellations, hints refused by strict mode or the sandbox,
+        // frozen memory, watchpoint callbacks failing and interruptions such as KeyboardInterrupt
+        // aren't errors of the program, so they are raised as is
+        let is_program_error = !self.watchpoint_failed
+            && Python::with_gil(|py| {
+                error.is_instance_of::<PyException>(py)
+                    && !error.is_instance_of::<ResourcesError>(py)
+                    && !error.is_instance_of::<RunCancelledError>(py)
+                    && !error.is_instance_of::<UnauthorizedHint>(py)
+                    && !error.is_instance_of::<HintSandboxError>(py)
+                    && !error.is_instance_of::<FrozenMemoryError>(py)
+            });
+        if !is_program_error {
+            return error;
+        }
         let pc = self.pyvm.vm.borrow().get_pc().offset;
         let instruction_location = get_location(pc, &self.inner, self.pyvm.failed_hint_index)
             .map(InstructionLocation::from);
@@ -669,18 +1177,13 @@ impl PyCairoRunner {
     }
 }
 
-#[derive(Clone, FromPyObject)]
-pub struct PyRunResources {
-    n_steps: Option<usize>,
-}
-
 #[pyclass]
 pub struct PyExecutionResources(ExecutionResources);
 
 #[pymethods]
 impl PyExecutionResources {
     #[getter]
-    fn n_steps(&self) -> usize {
+    pub fn n_steps(&self) -> usize {
         self.0.n_steps
     }
 
@@ -708,9 +1211,16 @@ impl PyExecutionResources {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::memory::MemoryValidationError;
     use crate::relocatable::PyMaybeRelocatable::RelocatableValue;
+    use crate::run_resources::{MemoryLimitError, StepsLimitError};
+    use crate::watchpoints::PyMemoryAccess;
     use cairo_rs::bigint;
     use num_bigint::BigInt;
+    use pyo3::{
+        exceptions::PyZeroDivisionError,
+        types::{PyDict, PyList},
+    };
     use std::env::temp_dir;
     use std::fs;
 
@@ -821,12 +1331,212 @@ mod test {
     }
 
     #[test]
-    fn initialize_runner() {
+    fn initialize_runner() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        runner.initialize().unwrap();
+    }
+
+    #[test]
+    fn step_returns_new_run_context() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        runner.initialize().unwrap();
+        let initial_pc = *runner.pyvm.vm.borrow().get_pc();
+
+        let run_context = runner.step().unwrap();
+
+        assert_ne!(run_context.pc(), PyRelocatable::from(initial_pc));
+        assert_eq!(run_context.pc(), runner.pyvm.run_context().pc());
+        assert_eq!(run_context.ap(), runner.get_ap().unwrap());
+    }
+
+    #[test]
+    fn step_n_stops_at_the_end_of_the_run() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+
+        let run_context = runner.step_n(usize::MAX).unwrap();
+
+        assert_eq!(run_context.pc(), end);
+    }
+
+    #[test]
+    fn run_until_breakpoint_stops_at_pc() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program.clone(), Some("main".to_string()), None, false).unwrap();
+        runner.initialize().unwrap();
+        let second_pc = runner.step_n(2).unwrap().pc();
+
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+        Python::with_gil(|py| {
+            runner
+                .add_breakpoint(second_pc.clone().into_py(py).as_ref(py), None)
+                .unwrap();
+        });
+        assert_eq!(runner.breakpoint_pcs(), vec![second_pc.clone()]);
+
+        assert_eq!(runner.run_until_breakpoint(None).unwrap().pc(), second_pc);
+
+        runner.clear_breakpoints();
+        assert_eq!(runner.run_until_breakpoint(None).unwrap().pc(), end);
+    }
+
+    #[test]
+    fn run_until_breakpoint_without_breakpoints_nor_end() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+
+        let error = runner.run_until_breakpoint(None).err().unwrap();
+        assert_eq!(
+            format!("{:?}", error),
+            format!("{:?}", PyValueError::new_err(NO_BREAKPOINTS_NOR_END_MSG))
+        );
+    }
+
+    #[test]
+    fn watch_memory_and_access_log() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        runner.initialize().unwrap();
+        let ap = runner.pyvm.vm.borrow().get_ap();
+
+        Python::with_gil(|py| {
+            let accesses = PyList::empty(py);
+            let watched = (PyRelocatable::from(ap), 2).into_py(py);
+            let id = runner
+                .watch(
+                    watched.as_ref(py),
+                    accesses.getattr("append").unwrap(),
+                    false,
+                )
+                .unwrap();
+            assert!(runner
+                .watch(watched.as_ref(py), 1.into_py(py).as_ref(py), false)
+                .is_err());
+            assert!(runner.memory().access_log(None).is_err());
+            runner.enable_access_log();
+
+            runner.step_n(3).unwrap();
+            let written = accesses
+                .iter()
+                .map(|access| access.extract::<PyMemoryAccess>().unwrap().address)
+                .collect::<Vec<_>>();
+            assert_eq!(written, vec![ap, ap + 1]);
+            assert!(runner.memory().access_log(None).unwrap().len() > 2);
+            assert_eq!(
+                runner
+                    .memory()
+                    .access_log(Some(PyRelocatable::from(ap)))
+                    .unwrap()
+                    .len(),
+                1
+            );
+
+            assert!(runner.unwatch(id));
+            assert!(!runner.unwatch(id));
+            runner.disable_access_log();
+            assert!(runner.memory().access_log(None).is_err());
+        });
+    }
+
+    #[test]
+    fn builtin_hint_write_rejected_by_validation_rule() {
+        let path = "cairo_programs/array_sum.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+        let segments = runner.pyvm.vm.borrow().memory.data.len();
+
+        Python::with_gil(|py| {
+            // Rejects the pointer that the `alloc` hint writes to the execution segment
+            let globals = PyDict::new(py);
+            let code = format!(
+                "def rejects_new_segments(memory, addr):\n    return getattr(memory[addr], 'segment_index', 0) < {segments}"
+            );
+            py.run(&code, Some(globals), None).unwrap();
+            runner
+                .memory()
+                .add_validation_rule(1, globals.get_item("rejects_new_segments").unwrap())
+                .unwrap();
+
+            let error = runner.run_until_pc(&end, None).unwrap_err();
+            let inner_error = error.value(py).getattr("inner_exc").unwrap();
+            assert!(inner_error
+                .is_instance(py.get_type::<MemoryValidationError>())
+                .unwrap());
+            assert!(inner_error.to_string().contains("rejects_new_segments"));
+        });
+    }
+
+    #[test]
+    fn watchpoint_callback_error() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        runner.initialize().unwrap();
+        let ap = runner.pyvm.vm.borrow().get_ap();
+
+        Python::with_gil(|py| {
+            let callback = py.eval("lambda access: 1 / 0", None, None).unwrap();
+            let watched = PyRelocatable::from(ap).into_py(py);
+            runner.watch(watched.as_ref(py), callback, false).unwrap();
+
+            let error = runner.step_n(3).unwrap_err();
+            assert!(error.is_instance_of::<PyZeroDivisionError>(py));
+            runner.clear_watchpoints();
+            assert!(runner.step_n(3).is_ok());
+        });
+    }
+
+    #[test]
+    fn restore_snapshot() {
         let path = "cairo_programs/fibonacci.json".to_string();
         let program = fs::read_to_string(path).unwrap();
         let mut runner =
             PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
-        runner.initialize().unwrap();
+        let end = runner.initialize().unwrap();
+        let run_context = runner.step_n(3).unwrap();
+
+        Python::with_gil(|py| {
+            let snapshot = runner.snapshot(py).unwrap();
+            assert_eq!(snapshot.run_context().pc(), run_context.pc());
+
+            let final_ap = Relocatable::from(&runner.run_until_breakpoint(None).unwrap().ap());
+            let last_cell = Relocatable::from((final_ap.segment_index, final_ap.offset - 1));
+            assert!(runner
+                .pyvm
+                .vm
+                .borrow()
+                .get_maybe(&last_cell)
+                .unwrap()
+                .is_some());
+
+            runner.restore(py, &snapshot).unwrap();
+            assert_eq!(runner.pyvm.run_context().pc(), run_context.pc());
+            assert_eq!(runner.pyvm.run_context().ap(), run_context.ap());
+            assert_eq!(runner.pyvm.vm.borrow().get_maybe(&last_cell), Ok(None));
+
+            // The restored run can be resumed up to the end again
+            assert_eq!(runner.run_until_breakpoint(None).unwrap().pc(), end);
+        });
     }
 
     #[test]
@@ -893,7 +1603,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
         let new_segment = runner.add_segment();
         assert_eq!(
@@ -926,7 +1636,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
 
         let expected_output: Vec<PyMaybeRelocatable> = vec![RelocatableValue(PyRelocatable {
@@ -958,7 +1668,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
 
         let expected_output: Vec<PyMaybeRelocatable> = vec![
@@ -996,7 +1706,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
 
         let expected_output = PyRelocatable::from((1, 8));
@@ -1020,7 +1730,7 @@ mod test {
         )
         .unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, Some("main"))
+            .cairo_run_py(false, None, None, None, None, Some("main"), None)
             .unwrap();
         // Make a copy of the builtin in order to insert a second "fake" one
         // BuiltinRunner api is private, so we can create a new one for this test
@@ -1063,7 +1773,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
 
         let expected_output = PyRelocatable::from((1, 0));
@@ -1087,7 +1797,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
 
         // Make a copy of the builtin in order to insert a second "fake" one
@@ -1125,7 +1835,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
 
         assert_eq!(runner.pyvm.vm.borrow().get_ap(), Relocatable::from((1, 41)));
@@ -1166,7 +1876,7 @@ mod test {
         let mut runner =
             PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
         Python::with_gil(|py| {
             assert_eq!(
@@ -1187,7 +1897,7 @@ mod test {
         let mut runner =
             PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
         Python::with_gil(|py| {
             assert_eq!(
@@ -1208,7 +1918,7 @@ mod test {
         let mut runner =
             PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
 
         Python::with_gil(|py| assert!(runner.get_segment_used_size(100, py).is_err()));
@@ -1226,7 +1936,7 @@ mod test {
             PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
 
         runner
-            .cairo_run_py(false, None, None, hint_locals, None, None)
+            .cairo_run_py(false, None, None, hint_locals, None, None, None)
             .unwrap();
 
         Python::with_gil(|py| assert!(runner.get_segment_used_size(100, py).is_err()));
@@ -1260,6 +1970,7 @@ mod test {
                     None,
                     None,
                     None,
+                    None,
                 )
                 .unwrap();
         });
@@ -1299,6 +2010,7 @@ mod test {
                     None,
                     None,
                     Some(false),
+                    None,
                 )
                 .unwrap();
         });
@@ -1344,6 +2056,7 @@ mod test {
                     None,
                     None,
                     Some(false),
+                    None,
                 )
                 .unwrap();
         });
@@ -1386,6 +2099,7 @@ mod test {
                     None,
                     None,
                     None,
+                    None,
                 )
                 .is_err());
         });
@@ -1418,11 +2132,88 @@ mod test {
                 None,
                 None,
                 None,
+                None,
+            );
+            assert!(result.is_ok());
+        });
+        // not_main writes 123 into [ap] before returning
+        let ap = runner.pyvm.vm.borrow().get_ap();
+        assert_eq!(
+            runner.pyvm.vm.borrow().get_maybe(&ap),
+            Ok(Some(MaybeRelocatable::from(bigint!(123))))
+        );
+    }
+
+    #[test]
+    fn run_from_entrypoint_with_full_name() {
+        let path = "cairo_programs/not_main.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner = PyCairoRunner::new(
+            program,
+            Some("main".to_string()),
+            Some("plain".to_string()),
+            false,
+        )
+        .unwrap();
+
+        runner
+            .initialize_function_runner()
+            .expect("Failed to initialize function runner");
+
+        Python::with_gil(|py| {
+            let result = runner.run_from_entrypoint(
+                py,
+                py.eval("'__main__.not_main'", None, None).unwrap(),
+                Vec::<&PyAny>::new().to_object(py),
+                None,
+                None,
+                Some(false),
+                None,
+                None,
+                None,
+                Some(true),
+            );
+            assert!(result.is_ok());
+        });
+    }
+
+    #[test]
+    fn run_from_entrypoint_with_unknown_name() {
+        let path = "cairo_programs/not_main.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner = PyCairoRunner::new(
+            program,
+            Some("main".to_string()),
+            Some("plain".to_string()),
+            false,
+        )
+        .unwrap();
+
+        runner
+            .initialize_function_runner()
+            .expect("Failed to initialize function runner");
+
+        Python::with_gil(|py| {
+            let result = runner.run_from_entrypoint(
+                py,
+                py.eval("'not_a_function'", None, None).unwrap(),
+                Vec::<&PyAny>::new().to_object(py),
+                None,
+                None,
+                Some(false),
+                None,
+                None,
+                None,
+                None,
             );
-            // using a named entrypoint in run_from_entrypoint is not implemented yet
             assert_eq!(
                 format!("{:?}", result),
-                format!("{:?}", Err::<(), PyErr>(PyNotImplementedError::new_err(())))
+                format!(
+                    "{:?}",
+                    Err::<(), PyErr>(PyValueError::new_err(
+                        "Unknown identifier 'not_a_function'."
+                    ))
+                )
             );
         });
     }
@@ -1453,7 +2244,12 @@ mod test {
                 None,
                 Some(false),
                 None,
-                Some(PyRunResources { n_steps: Some(0) }),
+                Some(
+                    PyCell::new(py, PyRunResources::new(Some(0), None, None, None))
+                        .unwrap()
+                        .as_ref(),
+                ),
+                None,
                 None,
             );
             assert!(result.is_err());
@@ -1467,6 +2263,166 @@ mod test {
         assert_eq!(pc_before_run, pc_after_run);
     }
 
+    #[test]
+    fn run_until_pc_reports_consumed_resources() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+
+        Python::with_gil(|py| {
+            let run_resources = PyCell::new(py, PyRunResources::default()).unwrap();
+            runner
+                .run_until_pc(&end, Some(run_resources.as_ref()))
+                .unwrap();
+
+            let run_resources = run_resources.borrow();
+            assert_eq!(
+                run_resources.consumed_steps,
+                runner.get_execution_resources().unwrap().n_steps()
+            );
+            assert!(run_resources.consumed_memory_cells > 0);
+            assert_eq!(run_resources.consumed_hints, 0);
+        });
+    }
+
+    #[test]
+    fn run_until_pc_steps_limit_without_hints() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+
+        Python::with_gil(|py| {
+            let run_resources =
+                PyCell::new(py, PyRunResources::new(Some(5), None, None, None)).unwrap();
+            let error = runner
+                .run_until_pc(&end, Some(run_resources.as_ref()))
+                .unwrap_err();
+
+            assert!(error.is_instance_of::<StepsLimitError>(py));
+            assert_eq!(run_resources.borrow().consumed_steps, 5);
+        });
+    }
+
+    #[test]
+    fn run_until_pc_memory_limit() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+
+        Python::with_gil(|py| {
+            let run_resources =
+                PyCell::new(py, PyRunResources::new(None, Some(1), None, None)).unwrap();
+            let error = runner
+                .run_until_pc(&end, Some(run_resources.as_ref()))
+                .unwrap_err();
+
+            assert!(error.is_instance_of::<MemoryLimitError>(py));
+            assert!(error.is_instance_of::<ResourcesError>(py));
+            assert_eq!(run_resources.borrow().consumed_steps, 1);
+        });
+    }
+
+    #[test]
+    fn register_hint() {
+        let path = "cairo_programs/hint_print_vars.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let code =
+            "c = ids.a + ids.b\nprint(\"a: \", ids.a)\nprint(\"b: \", ids.b)\nprint(\"c: \", c)";
+
+        Python::with_gil(|py| {
+            assert!(runner
+                .register_hint(code.to_string(), py.eval("1", None, None).unwrap())
+                .unwrap_err()
+                .is_instance_of::<PyTypeError>(py));
+
+            let func = py
+                .eval(
+                    "lambda vm, ids, memory, scopes: scopes.__setitem__('c', ids.a + ids.b)",
+                    None,
+                    None,
+                )
+                .unwrap();
+            runner.register_hint(code.to_string(), func).unwrap();
+        });
+        let end = runner.initialize().unwrap();
+        runner.run_until_pc(&end, None).unwrap();
+
+        assert_eq!(
+            runner
+                .inner
+                .exec_scopes
+                .get_local_variables()
+                .unwrap()
+                .get("c")
+                .and_then(|c| c.downcast_ref::<BigInt>()),
+            Some(&BigInt::from(3))
+        );
+    }
+
+    #[test]
+    fn strict_mode_refuses_unknown_hints() {
+        let path = "cairo_programs/hint_print_vars.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        runner.enable_strict_mode(vec![String::from("print(ap)")]);
+        assert!(runner.strict_mode());
+        let end = runner.initialize().unwrap();
+
+        let error = runner.run_until_pc(&end, None).unwrap_err();
+        Python::with_gil(|py| {
+            assert!(error.is_instance_of::<UnauthorizedHint>(py));
+            assert_eq!(
+                error
+                    .value(py)
+                    .getattr("pc")
+                    .unwrap()
+                    .extract::<PyRelocatable>()
+                    .unwrap(),
+                PyRelocatable::from(*runner.pyvm.vm.borrow().get_pc())
+            );
+            assert_eq!(
+                error
+                    .value(py)
+                    .getattr("source")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "c = ids.a + ids.b\nprint(\"a: \", ids.a)\nprint(\"b: \", ids.b)\nprint(\"c: \", c)"
+            );
+        });
+
+        runner.disable_strict_mode();
+        assert!(runner.run_until_pc(&end, None).is_ok());
+    }
+
+    #[test]
+    fn cancelled_run_can_be_inspected() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+        let pc_before_run = runner.pyvm.run_context().pc();
+
+        runner.cancel_token().cancel();
+        let error = runner.run_until_pc(&end, None).unwrap_err();
+
+        Python::with_gil(|py| assert!(error.is_instance_of::<RunCancelledError>(py)));
+        assert_eq!(runner.pyvm.run_context().pc(), pc_before_run);
+
+        runner.cancel_token().reset();
+        assert!(runner.step().is_ok());
+    }
+
     #[test]
     fn run_from_entrypoint_with_invalid_entrypoint() {
         let path = "cairo_programs/not_main.json".to_string();
@@ -1494,6 +2450,7 @@ mod test {
                 None,
                 None,
                 None,
+                None,
             );
             assert_eq!(
                 format!("{:?}", result),
@@ -1517,7 +2474,7 @@ mod test {
         )
         .unwrap();
 
-        runner.initialize_segments();
+        runner.initialize_segments().unwrap();
 
         Python::with_gil(|py| {
             runner
@@ -1534,6 +2491,7 @@ mod test {
                     None,
                     None,
                     None,
+                    None,
                 )
                 .unwrap();
             assert!(!runner.hint_locals.is_empty());
@@ -1561,7 +2519,7 @@ mod test {
         )
         .unwrap();
 
-        runner.initialize_segments();
+        runner.initialize_segments().unwrap();
 
         Python::with_gil(|py| {
             runner
@@ -1578,6 +2536,7 @@ mod test {
                     None,
                     None,
                     None,
+                    None,
                 )
                 .unwrap();
             assert!(!runner.static_locals.as_ref().unwrap().is_empty());
@@ -1608,7 +2567,7 @@ mod test {
             )
             .unwrap();
 
-            runner.initialize_segments();
+            runner.initialize_segments().unwrap();
 
             let args = MyIterator {
                 iter: Box::new(
@@ -1626,6 +2585,7 @@ mod test {
                 None,
                 None,
                 None,
+                None,
             )
         };
         Python::with_gil(|py| {
@@ -1649,7 +2609,7 @@ mod test {
         )
         .unwrap();
 
-        runner.initialize_segments();
+        runner.initialize_segments().unwrap();
 
         Python::with_gil(|py| {
             let array = vec![
@@ -1670,6 +2630,7 @@ mod test {
                 None,
                 None,
                 None,
+                None,
             );
 
             assert!(result.is_ok());
@@ -1751,7 +2712,7 @@ mod test {
         )
         .unwrap();
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .unwrap();
         assert_eq! {
             PyRelocatable::from((1,2)),
@@ -1993,6 +2954,7 @@ mod test {
                     Python::with_gil(|py| -> PyObject { 100.to_object(py) }),
                 )])),
                 None,
+                None
             )
             .is_ok());
     }
@@ -2018,6 +2980,7 @@ mod test {
                     "__find_element_max_size".to_string(),
                     Python::with_gil(|py| -> PyObject { 1.to_object(py) }),
                 )])),
+                None,
                 None
             )
             .is_err());
@@ -2031,7 +2994,7 @@ mod test {
             PyCairoRunner::new(program, None, Some("small".to_string()), false).unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, Some("main"))
+            .cairo_run_py(false, None, None, None, None, Some("main"), None)
             .expect("Call to PyCairoRunner::cairo_run_py() failed.");
     }
 
@@ -2064,7 +3027,7 @@ mod test {
             .unwrap();
 
             runner
-                .cairo_run_py(false, None, None, None, None, None)
+                .cairo_run_py(false, None, None, None, None, None, None)
                 .expect("Call to PyCairoRunner::cairo_run_py");
 
             let mut ap = runner.get_ap().unwrap();
@@ -2459,7 +3422,7 @@ mod test {
         _ = fs::remove_file(trace_path);
 
         runner
-            .cairo_run_py(false, Some(trace_path), None, None, None, None)
+            .cairo_run_py(false, Some(trace_path), None, None, None, None, None)
             .expect("Call to PyCairoRunner::cairo_run_py() failed.");
 
         // We simply check if file exists
@@ -2482,7 +3445,7 @@ mod test {
 
         let trace_path = "cairo_programs";
 
-        let result = runner.cairo_run_py(false, Some(trace_path), None, None, None, None);
+        let result = runner.cairo_run_py(false, Some(trace_path), None, None, None, None, None);
 
         assert!(result.is_err());
     }
@@ -2505,7 +3468,7 @@ mod test {
         _ = fs::remove_file(memory_path);
 
         runner
-            .cairo_run_py(false, None, Some(memory_path), None, None, None)
+            .cairo_run_py(false, None, Some(memory_path), None, None, None, None)
             .expect("Call to PyCairoRunner::cairo_run_py() failed.");
 
         // We simply check if file exists
@@ -2514,6 +3477,79 @@ mod test {
         _ = fs::remove_file(memory_path);
     }
 
+    #[test]
+    fn relocated_memory_and_trace() {
+        let path = String::from("cairo_programs/fibonacci.json");
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner = PyCairoRunner::new(
+            program,
+            Some("main".to_string()),
+            Some("small".to_string()),
+            false,
+        )
+        .unwrap();
+        assert!(runner.relocated_memory().is_err());
+
+        let trace_path = temp_dir().join("fibonacci_relocated.trace");
+        let trace_path = trace_path.to_str().unwrap();
+        let memory_path = temp_dir().join("fibonacci_relocated.memory");
+        let memory_path = memory_path.to_str().unwrap();
+        runner
+            .cairo_run_py(
+                false,
+                Some(trace_path),
+                Some(memory_path),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        let relocated_memory = runner.relocated_memory().unwrap();
+        let relocated_trace = runner.relocated_trace().unwrap();
+        assert_eq!(
+            relocated_memory.len(),
+            runner.inner.relocated_memory.iter().flatten().count()
+        );
+        assert!(!relocated_trace.is_empty());
+        Python::with_gil(|py| {
+            assert_eq!(
+                runner.relocated_memory_bytes(py).unwrap().as_bytes(),
+                fs::read(memory_path).unwrap()
+            );
+            assert_eq!(
+                runner.relocated_trace_bytes(py).unwrap().as_bytes(),
+                fs::read(trace_path).unwrap()
+            );
+        });
+
+        // The trace is also kept without a trace_file when trace_enabled is set
+        let program = fs::read_to_string("cairo_programs/fibonacci.json").unwrap();
+        let new_runner = || {
+            PyCairoRunner::new(
+                program.clone(),
+                Some("main".to_string()),
+                Some("small".to_string()),
+                false,
+            )
+            .unwrap()
+        };
+        let mut untraced_runner = new_runner();
+        untraced_runner
+            .cairo_run_py(false, None, None, None, None, None, None)
+            .unwrap();
+        assert!(untraced_runner.relocated_trace().is_err());
+        let mut traced_runner = new_runner();
+        traced_runner
+            .cairo_run_py(false, None, None, None, None, None, Some(true))
+            .unwrap();
+        assert_eq!(traced_runner.relocated_trace().unwrap(), relocated_trace);
+
+        _ = fs::remove_file(trace_path);
+        _ = fs::remove_file(memory_path);
+    }
+
     #[test]
     fn cairo_run_with_nonexistent_memory_file() {
         let path = String::from("cairo_programs/fibonacci.json");
@@ -2528,7 +3564,7 @@ mod test {
 
         let memory_path = "cairo_programs";
 
-        let result = runner.cairo_run_py(false, None, Some(memory_path), None, None, None);
+        let result = runner.cairo_run_py(false, None, Some(memory_path), None, None, None, None);
 
         assert!(result.is_err());
     }
@@ -2545,13 +3581,13 @@ mod test {
         )
         .unwrap();
 
-        let result = runner.cairo_run_py(false, None, None, None, None, None);
+        let result = runner.cairo_run_py(false, None, None, None, None, None, None);
 
         assert!(result.is_ok());
 
         let exec_res = runner.get_execution_resources().unwrap();
 
-        // n_steps is 0 because trace is disabled when trace_file is None
+        // n_steps is 0 because trace is disabled without trace_file or trace_enabled
         assert_eq!(exec_res.n_steps(), 0);
         assert_eq!(exec_res.n_memory_holes(), 0);
         assert_eq!(
@@ -2574,6 +3610,82 @@ mod test {
         assert!(runner.mark_as_accessed((0, 0).into(), 3).is_err());
     }
 
+    #[test]
+    fn get_accessed_addresses() {
+        let path = String::from("cairo_programs/fibonacci.json");
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner = PyCairoRunner::new(
+            program,
+            Some("main".to_string()),
+            Some("small".to_string()),
+            false,
+        )
+        .unwrap();
+        runner
+            .cairo_run_py(false, None, None, None, None, None, None)
+            .unwrap();
+
+        let accessed_addresses = runner.get_accessed_addresses(Some(1)).unwrap();
+        assert!(!accessed_addresses.is_empty());
+        assert!(accessed_addresses
+            .iter()
+            .all(|address| address.segment_index == 1));
+        assert!(accessed_addresses
+            .windows(2)
+            .all(|pair| pair[0].offset < pair[1].offset));
+        assert!(runner.get_accessed_addresses(None).unwrap().len() > accessed_addresses.len());
+        assert!(runner.get_accessed_addresses(Some(100)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn frozen_memory_stops_the_run() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        let end = runner.initialize().unwrap();
+        let run_context = runner.step_n(2).unwrap();
+        let snapshot = Python::with_gil(|py| runner.snapshot(py)).unwrap();
+
+        runner.memory().freeze();
+        Python::with_gil(|py| {
+            let error = runner.step_n(1).unwrap_err();
+            assert!(error.is_instance_of::<FrozenMemoryError>(py));
+            let error = runner.run_until_breakpoint(Some(end)).unwrap_err();
+            assert!(error.is_instance_of::<FrozenMemoryError>(py));
+
+            let ptr = PyRelocatable::from((1, 100));
+            let error = runner
+                .insert(&ptr, PyMaybeRelocatable::Int(bigint!(1)))
+                .unwrap_err();
+            assert!(error.is_instance_of::<FrozenMemoryError>(py));
+            let error = runner
+                .write_arg(
+                    py,
+                    PyMaybeRelocatable::RelocatableValue(ptr.clone()),
+                    vec![1].into_py(py),
+                    true,
+                )
+                .unwrap_err();
+            assert!(error.is_instance_of::<FrozenMemoryError>(py));
+            assert!(runner.get(py, &ptr).unwrap().is_none());
+
+            let segments = runner.pyvm.vm.borrow().memory.data.len();
+            let errors = [
+                runner.gen_arg(py, vec![1].into_py(py), true).err(),
+                runner.initialize().err(),
+                runner.initialize_segments().err(),
+                runner.initialize_function_runner().err(),
+                runner.restore(py, &snapshot).err(),
+            ];
+            for error in errors {
+                assert!(error.unwrap().is_instance_of::<FrozenMemoryError>(py));
+            }
+            assert_eq!(runner.pyvm.vm.borrow().memory.data.len(), segments);
+        });
+        assert_eq!(runner.pyvm.run_context().pc(), run_context.pc());
+    }
+
     #[test]
     fn get_return_values_ok() {
         let path = String::from("cairo_programs/fibonacci.json");
@@ -2587,7 +3699,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .expect("Call to PyCairoRunner::cairo_run_py() failed.");
 
         Python::with_gil(|py| {
@@ -2620,7 +3732,7 @@ mod test {
         .unwrap();
 
         runner
-            .cairo_run_py(true, None, None, None, None, None)
+            .cairo_run_py(true, None, None, None, None, None, None)
             .expect("Call to PyCairoRunner::cairo_run_py() failed.");
 
         Python::with_gil(|py| {
@@ -2643,7 +3755,7 @@ mod test {
         .unwrap();
 
         assert!(runner
-            .cairo_run_py(false, None, None, None, None, None)
+            .cairo_run_py(false, None, None, None, None, None, None)
             .is_ok());
     }
 }
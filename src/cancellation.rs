@@ -0,0 +1,78 @@
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+create_exception!(cairo_rs_py, RunCancelledError, PyException);
+
+/// Number of steps executed between two checks for pending Python signals.
+pub(crate) const SIGNALS_CHECK_INTERVAL: usize = 1024;
+
+const RUN_CANCELLED_MSG: &str = "Error: The run was cancelled.";
+
+/// A flag that stops the run of the runner it belongs to before its next step.
+/// It can be shared with, and triggered from, any thread. Once cancelled, it stays so until
+/// `reset` is called.
+#[pyclass(name = "CancelToken")]
+#[derive(Clone, Debug, Default)]
+pub struct PyCancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl PyCancelToken {
+    #[new]
+    pub fn new() -> PyCancelToken {
+        PyCancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed)
+    }
+
+    #[getter]
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl PyCancelToken {
+    pub(crate) fn check(&self) -> PyResult<()> {
+        if self.is_cancelled() {
+            return Err(RunCancelledError::new_err(RUN_CANCELLED_MSG));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cancel_token_is_shared() {
+        let token = PyCancelToken::new();
+        let other_thread_token = token.clone();
+        assert!(token.check().is_ok());
+
+        std::thread::spawn(move || other_thread_token.cancel())
+            .join()
+            .unwrap();
+
+        assert!(token.is_cancelled());
+        Python::with_gil(|py| {
+            assert!(token
+                .check()
+                .unwrap_err()
+                .is_instance_of::<RunCancelledError>(py))
+        });
+
+        token.reset();
+        assert!(token.check().is_ok());
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    relocatable::{PyMaybeRelocatable, PyRelocatable},
+    utils::to_py_error,
+};
+use cairo_rs::{
+    any_box,
+    hint_processor::builtin_hint_processor::dict_manager::{DictManager, DictTracker},
+    types::{exec_scope::ExecutionScopes, relocatable::Relocatable},
+    vm::vm_core::VirtualMachine,
+};
+use num_bigint::BigInt;
+use pyo3::{
+    exceptions::{PyKeyError, PyValueError},
+    prelude::*,
+    types::{PyIterator, PyList},
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Name of the scope variable holding the `DictManager` used by the builtin dict hints.
+const DICT_MANAGER_VARIABLE: &str = "dict_manager";
+
+/// `__dict_manager` of the Python hints: a view of the `DictManager` of the current scope, so
+/// that dictionaries created or modified by builtin hints are seen by Python hints and the other
+/// way around. It follows the interface of cairo-lang's `DictManager`.
+#[pyclass(unsendable, name = "DictManager")]
+pub struct PyDictManager {
+    manager: Rc<RefCell<DictManager>>,
+    vm: Rc<RefCell<VirtualMachine>>,
+    /// Whether `manager` is the one of the scope, rather than a new one.
+    in_scope: bool,
+}
+
+#[pymethods]
+impl PyDictManager {
+    /// Creates a dictionary in a new segment, returning its base. `segments` is only taken for
+    /// compatibility with cairo-lang.
+    pub fn new_dict(
+        &self,
+        _segments: &PyAny,
+        initial_dict: HashMap<BigInt, BigInt>,
+    ) -> PyResult<PyMaybeRelocatable> {
+        self.manager
+            .borrow_mut()
+            .new_dict(&mut self.vm.borrow_mut(), initial_dict)
+            .map(PyMaybeRelocatable::from)
+            .map_err(to_py_error)
+    }
+
+    #[args(initial_dict = "None")]
+    pub fn new_default_dict(
+        &self,
+        _segments: &PyAny,
+        default_value: BigInt,
+        initial_dict: Option<HashMap<BigInt, BigInt>>,
+    ) -> PyResult<PyMaybeRelocatable> {
+        self.manager
+            .borrow_mut()
+            .new_default_dict(&mut self.vm.borrow_mut(), &default_value, initial_dict)
+            .map(PyMaybeRelocatable::from)
+            .map_err(to_py_error)
+    }
+
+    /// Returns the tracker of the dictionary whose current pointer is `dict_ptr`.
+    pub fn get_tracker(&self, dict_ptr: &PyRelocatable) -> PyResult<PyDictTracker> {
+        let dict_ptr = Relocatable::from(dict_ptr);
+        self.with_tracker(dict_ptr.segment_index, |tracker| {
+            if tracker.current_ptr != dict_ptr {
+                return Err(PyValueError::new_err(format!(
+                    "Wrong dict pointer supplied. Got {}, expected {}.",
+                    PyRelocatable::from(dict_ptr).__repr__(),
+                    PyRelocatable::from(tracker.current_ptr).__repr__()
+                )));
+            }
+            Ok(())
+        })?;
+        Ok(PyDictTracker {
+            manager: Rc::clone(&self.manager),
+            segment_index: dict_ptr.segment_index,
+        })
+    }
+
+    /// Returns a copy of the contents of the dictionary whose current pointer is `dict_ptr`.
+    pub fn get_dict(&self, dict_ptr: &PyRelocatable) -> PyResult<HashMap<BigInt, BigInt>> {
+        self.get_tracker(dict_ptr)?;
+        self.with_tracker(dict_ptr.segment_index, |tracker| {
+            Ok(tracker.get_dictionary_copy())
+        })
+    }
+}
+
+impl PyDictManager {
+    /// Wraps the `DictManager` of the current scope, or a new one if there is none.
+    pub(crate) fn from_scope(
+        exec_scopes: &ExecutionScopes,
+        vm: Rc<RefCell<VirtualMachine>>,
+    ) -> PyDictManager {
+        let (manager, in_scope) = match exec_scopes.get_dict_manager() {
+            Ok(manager) => (manager, true),
+            Err(_) => (Rc::new(RefCell::new(DictManager::new())), false),
+        };
+        PyDictManager {
+            manager,
+            vm,
+            in_scope,
+        }
+    }
+
+    /// Adds a new manager to the current scope once a hint has created dictionaries with it, so
+    /// that the hints that run next, builtin or not, find them.
+    pub(crate) fn add_to_scope(&self, exec_scopes: &mut ExecutionScopes) {
+        if !self.in_scope && !self.manager.borrow().trackers.is_empty() {
+            exec_scopes.assign_or_update_variable(
+                DICT_MANAGER_VARIABLE,
+                any_box!(Rc::clone(&self.manager)),
+            );
+        }
+    }
+
+    fn with_tracker<T>(
+        &self,
+        segment_index: isize,
+        f: impl FnOnce(&mut DictTracker) -> PyResult<T>,
+    ) -> PyResult<T> {
+        with_tracker(&self.manager, segment_index, f)
+    }
+}
+
+fn with_tracker<T>(
+    manager: &RefCell<DictManager>,
+    segment_index: isize,
+    f: impl FnOnce(&mut DictTracker) -> PyResult<T>,
+) -> PyResult<T> {
+    match manager.borrow_mut().trackers.get_mut(&segment_index) {
+        Some(tracker) => f(tracker),
+        None => Err(PyValueError::new_err(format!(
+            "No dict tracker found for segment {segment_index}."
+        ))),
+    }
+}
+
+/// A live view of the tracker of a dictionary of the Rust `DictManager`.
+#[pyclass(unsendable, name = "DictTracker")]
+pub struct PyDictTracker {
+    manager: Rc<RefCell<DictManager>>,
+    segment_index: isize,
+}
+
+#[pymethods]
+impl PyDictTracker {
+    #[getter]
+    pub fn current_ptr(&self) -> PyResult<PyRelocatable> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            Ok(PyRelocatable::from(tracker.current_ptr))
+        })
+    }
+
+    #[setter]
+    pub fn set_current_ptr(&self, current_ptr: &PyRelocatable) -> PyResult<()> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            tracker.current_ptr = Relocatable::from(current_ptr);
+            Ok(())
+        })
+    }
+
+    #[getter]
+    pub fn data(&self) -> PyDictTrackerData {
+        PyDictTrackerData {
+            manager: Rc::clone(&self.manager),
+            segment_index: self.segment_index,
+        }
+    }
+}
+
+/// The contents of a dictionary of the Rust `DictManager`. Reading a missing key of a default
+/// dictionary adds it with the default value, as with Python's `defaultdict`.
+#[pyclass(unsendable, name = "DictTrackerData")]
+pub struct PyDictTrackerData {
+    manager: Rc<RefCell<DictManager>>,
+    segment_index: isize,
+}
+
+#[pymethods]
+impl PyDictTrackerData {
+    pub fn __getitem__(&self, key: BigInt) -> PyResult<BigInt> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            tracker
+                .get_value(&key)
+                .map(Clone::clone)
+                .map_err(|_| PyKeyError::new_err(key.to_string()))
+        })
+    }
+
+    pub fn __setitem__(&self, key: BigInt, value: BigInt) -> PyResult<()> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            tracker.insert_value(&key, &value);
+            Ok(())
+        })
+    }
+
+    pub fn __contains__(&self, key: BigInt) -> PyResult<bool> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            Ok(tracker.get_dictionary_ref().contains_key(&key))
+        })
+    }
+
+    pub fn __len__(&self) -> PyResult<usize> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            Ok(tracker.get_dictionary_ref().len())
+        })
+    }
+
+    /// Iterates over the keys the dictionary has when called.
+    pub fn __iter__<'py>(&self, py: Python<'py>) -> PyResult<&'py PyIterator> {
+        let keys = with_tracker(&self.manager, self.segment_index, |tracker| {
+            Ok(tracker
+                .get_dictionary_ref()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>())
+        })?;
+        PyIterator::from_object(py, PyList::new(py, keys))
+    }
+
+    /// Returns the value of `key`, or `default` if it's missing. Unlike indexing, it doesn't add
+    /// missing keys to default dictionaries.
+    #[args(default = "None")]
+    pub fn get(&self, py: Python, key: BigInt, default: Option<PyObject>) -> PyResult<PyObject> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            Ok(match tracker.get_dictionary_ref().get(&key) {
+                Some(value) => value.to_object(py),
+                None => default.unwrap_or_else(|| py.None()),
+            })
+        })
+    }
+
+    pub fn items(&self) -> PyResult<Vec<(BigInt, BigInt)>> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            Ok(tracker
+                .get_dictionary_ref()
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect())
+        })
+    }
+
+    /// Returns a copy of the contents of the dictionary as a Python dict.
+    pub fn copy(&self) -> PyResult<HashMap<BigInt, BigInt>> {
+        with_tracker(&self.manager, self.segment_index, |tracker| {
+            Ok(tracker.get_dictionary_copy())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vm_core::PyVM;
+    use num_bigint::Sign;
+
+    #[test]
+    fn dict_manager_is_shared_with_scope() {
+        let vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let mut exec_scopes = ExecutionScopes::new();
+        let dict_manager = PyDictManager::from_scope(&exec_scopes, vm.get_vm());
+        dict_manager.add_to_scope(&mut exec_scopes);
+        assert!(exec_scopes.get_dict_manager().is_err());
+
+        Python::with_gil(|py| {
+            let dict_ptr = dict_manager
+                .new_dict(
+                    py.None().into_ref(py),
+                    HashMap::from([(BigInt::from(1), BigInt::from(2))]),
+                )
+                .unwrap();
+            let dict_ptr = match dict_ptr {
+                PyMaybeRelocatable::RelocatableValue(dict_ptr) => dict_ptr,
+                PyMaybeRelocatable::Int(_) => panic!("Expected a dict pointer"),
+            };
+            let tracker = dict_manager.get_tracker(&dict_ptr).unwrap();
+            let data = tracker.data();
+            assert_eq!(data.__getitem__(BigInt::from(1)).unwrap(), BigInt::from(2));
+            assert!(data.__getitem__(BigInt::from(5)).is_err());
+            data.__setitem__(BigInt::from(5), BigInt::from(6)).unwrap();
+            assert_eq!(data.__len__().unwrap(), 2);
+
+            tracker
+                .set_current_ptr(&PyRelocatable::from((dict_ptr.segment_index, 3)))
+                .unwrap();
+            assert!(dict_manager.get_tracker(&dict_ptr).is_err());
+        });
+        dict_manager.add_to_scope(&mut exec_scopes);
+
+        // The scope holds the same manager
+        let manager = exec_scopes.get_dict_manager().unwrap();
+        let manager = manager.borrow();
+        let tracker = manager.trackers.values().next().unwrap();
+        assert_eq!(
+            tracker.get_dictionary_copy(),
+            HashMap::from([
+                (BigInt::from(1), BigInt::from(2)),
+                (BigInt::from(5), BigInt::from(6))
+            ])
+        );
+        assert_eq!(tracker.current_ptr.offset, 3);
+    }
+}
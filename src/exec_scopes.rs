@@ -0,0 +1,210 @@
+use crate::{
+    cairo_runner::PyCairoRunner,
+    scope_manager::{assign_scope_variable_from_py, scope_variable_from_py, scope_variable_to_py},
+    utils::to_py_error,
+};
+use pyo3::{
+    exceptions::{PyIndexError, PyRuntimeError, PyTypeError},
+    prelude::*,
+};
+use std::{any::Any, collections::HashMap};
+
+const RUNNER_BUSY_MSG: &str = "The execution scopes of a runner can't be used while it runs";
+
+/// A live view of the execution scopes of a runner, as returned by `CairoRunner.exec_scopes`.
+/// Variables are read and written in the current (innermost) scope, as hints do.
+#[pyclass(unsendable, name = "ExecutionScopes")]
+pub struct PyExecutionScopes {
+    runner: Py<PyCairoRunner>,
+}
+
+#[pymethods]
+impl PyExecutionScopes {
+    /// Number of scopes, the main one included.
+    #[getter]
+    pub fn depth(&self, py: Python) -> PyResult<usize> {
+        Ok(self.runner(py)?.exec_scopes_ref().data.len())
+    }
+
+    /// Returns the names of the variables of the scope at `depth` (0 being the main scope), or of
+    /// the current scope if no depth is given.
+    #[args(depth = "None")]
+    pub fn variables(&self, py: Python, depth: Option<usize>) -> PyResult<Vec<String>> {
+        let runner = self.runner(py)?;
+        let exec_scopes = runner.exec_scopes_ref();
+        let depth = depth.unwrap_or_else(|| exec_scopes.data.len().saturating_sub(1));
+        let scope = exec_scopes
+            .data
+            .get(depth)
+            .ok_or_else(|| PyIndexError::new_err(format!("There is no scope at depth {depth}.")))?;
+        let mut names = scope.keys().cloned().collect::<Vec<_>>();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the value of a variable of the current scope, or `default` if it isn't defined.
+    #[args(default = "None")]
+    pub fn get(&self, py: Python, name: &str, default: Option<PyObject>) -> PyResult<PyObject> {
+        let runner = self.runner(py)?;
+        let value = runner
+            .exec_scopes_ref()
+            .data
+            .last()
+            .and_then(|scope| scope.get(name));
+        match value {
+            Some(value) => scope_variable_to_py(py, value.as_ref()).ok_or_else(|| {
+                PyTypeError::new_err(format!(
+                    "Scope variable '{name}' has a type that can't be read from Python"
+                ))
+            }),
+            None => Ok(default.unwrap_or_else(|| py.None())),
+        }
+    }
+
+    /// Sets a variable of the current scope. If it replaces a value set by a builtin hint, its
+    /// type is kept when possible.
+    pub fn set(&self, py: Python, name: &str, value: &PyAny) -> PyResult<()> {
+        assign_scope_variable_from_py(self.runner_mut(py)?.exec_scopes_mut(), name, value);
+        Ok(())
+    }
+
+    /// Enters a new scope with the given variables, as `vm_enter_scope` does.
+    #[args(variables = "None")]
+    pub fn push(&self, py: Python, variables: Option<HashMap<String, PyObject>>) -> PyResult<()> {
+        let scope = variables
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| (name, scope_variable_from_py(value.as_ref(py), None)))
+            .collect::<HashMap<String, Box<dyn Any>>>();
+        self.runner_mut(py)?.exec_scopes_mut().enter_scope(scope);
+        Ok(())
+    }
+
+    /// Exits the current scope, as `vm_exit_scope` does. The main scope can't be exited.
+    pub fn pop(&self, py: Python) -> PyResult<()> {
+        self.runner_mut(py)?
+            .exec_scopes_mut()
+            .exit_scope()
+            .map_err(to_py_error)
+    }
+}
+
+impl PyExecutionScopes {
+    pub(crate) fn new(runner: Py<PyCairoRunner>) -> PyExecutionScopes {
+        PyExecutionScopes { runner }
+    }
+
+    /// Borrows the runner, which fails while it runs, as when called from a hint or callback.
+    fn runner<'py>(&'py self, py: Python<'py>) -> PyResult<PyRef<'py, PyCairoRunner>> {
+        self.runner
+            .try_borrow(py)
+            .map_err(|_| PyRuntimeError::new_err(RUNNER_BUSY_MSG))
+    }
+
+    fn runner_mut<'py>(&'py self, py: Python<'py>) -> PyResult<PyRefMut<'py, PyCairoRunner>> {
+        self.runner
+            .try_borrow_mut(py)
+            .map_err(|_| PyRuntimeError::new_err(RUNNER_BUSY_MSG))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::relocatable::PyRelocatable;
+    use cairo_rs::{any_box, bigint};
+    use num_bigint::BigInt;
+    use pyo3::types::PyDict;
+    use std::fs;
+
+    #[test]
+    fn exec_scopes_view() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let runner = PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+
+        Python::with_gil(|py| {
+            let runner = Py::new(py, runner).unwrap();
+            let exec_scopes = PyExecutionScopes::new(runner.clone_ref(py));
+            assert_eq!(exec_scopes.depth(py).unwrap(), 1);
+            assert!(exec_scopes.pop(py).is_err());
+
+            runner
+                .borrow_mut(py)
+                .exec_scopes_mut()
+                .assign_or_update_variable("n", any_box!(bigint!(3)));
+            exec_scopes
+                .push(
+                    py,
+                    Some(HashMap::from([(String::from("m"), 5.to_object(py))])),
+                )
+                .unwrap();
+            assert_eq!(exec_scopes.depth(py).unwrap(), 2);
+            assert_eq!(exec_scopes.variables(py, None).unwrap(), vec!["m"]);
+            assert_eq!(exec_scopes.variables(py, Some(0)).unwrap(), vec!["n"]);
+            assert!(exec_scopes.variables(py, Some(2)).is_err());
+            assert_eq!(
+                exec_scopes
+                    .get(py, "m", None)
+                    .unwrap()
+                    .extract::<usize>(py)
+                    .unwrap(),
+                5
+            );
+            assert!(exec_scopes.get(py, "n", None).unwrap().is_none(py));
+
+            exec_scopes.pop(py).unwrap();
+            exec_scopes
+                .set(py, "n", py.eval("4", None, None).unwrap())
+                .unwrap();
+            assert_eq!(
+                runner.borrow_mut(py).exec_scopes_mut().data[0]["n"].downcast_ref::<BigInt>(),
+                Some(&bigint!(4))
+            );
+        });
+    }
+
+    #[test]
+    fn exec_scopes_while_running() {
+        let path = "cairo_programs/fibonacci.json".to_string();
+        let program = fs::read_to_string(path).unwrap();
+        let mut runner =
+            PyCairoRunner::new(program, Some("main".to_string()), None, false).unwrap();
+        runner.initialize().unwrap();
+        let ap = runner.pyvm.vm.borrow().get_ap();
+
+        Python::with_gil(|py| {
+            let runner = Py::new(py, runner).unwrap();
+            let globals = PyDict::new(py);
+            globals
+                .set_item(
+                    "scopes",
+                    PyCell::new(py, PyExecutionScopes::new(runner.clone_ref(py))).unwrap(),
+                )
+                .unwrap();
+            let code = r#"
+errors = []
+def callback(access):
+    try:
+        scopes.depth
+    except RuntimeError as error:
+        errors.append(error)
+"#;
+            py.run(code, Some(globals), None).unwrap();
+            let watched = (PyRelocatable::from(ap), 1).into_py(py);
+            runner
+                .borrow_mut(py)
+                .watch(
+                    watched.as_ref(py),
+                    globals.get_item("callback").unwrap(),
+                    false,
+                )
+                .unwrap();
+
+            runner.borrow_mut(py).step_n(3).unwrap();
+            let errors = py.eval("len(errors)", Some(globals), None).unwrap();
+            assert_eq!(errors.extract::<usize>().unwrap(), 1);
+            assert_eq!(PyExecutionScopes::new(runner).depth(py).unwrap(), 1);
+        });
+    }
+}
@@ -0,0 +1,177 @@
+use pyo3::{
+    exceptions::{PyTypeError, PyValueError},
+    PyErr,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const MAIN_SCOPE: &str = "__main__";
+// Bounds alias resolution so that a cycle of aliases can't hang the runner.
+const MAX_ALIAS_DEPTH: usize = 64;
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct IdentifierInfo {
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    pub pc: Option<usize>,
+    pub destination: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProgramIdentifiers {
+    #[serde(default)]
+    identifiers: HashMap<String, IdentifierInfo>,
+}
+
+/// Resolves names of the loaded program's identifiers, following the rules of cairo-lang's
+/// `IdentifierManager`: names are looked up relative to the `__main__` scope unless
+/// `full_name_lookup` is requested, and aliases are followed until a definition is found.
+#[derive(Debug, Clone, Default)]
+pub struct IdentifierManager {
+    identifiers: HashMap<String, IdentifierInfo>,
+}
+
+impl IdentifierManager {
+    pub fn from_program_json(program: &str) -> Result<IdentifierManager, PyErr> {
+        let program: ProgramIdentifiers =
+            serde_json::from_str(program).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(IdentifierManager {
+            identifiers: program.identifiers,
+        })
+    }
+
+    /// Returns the pc of the function (or label) called `name`.
+    pub fn get_label(&self, name: &str, full_name_lookup: bool) -> Result<usize, PyErr> {
+        let full_name = if full_name_lookup {
+            name.to_string()
+        } else {
+            format!("{MAIN_SCOPE}.{name}")
+        };
+
+        let (resolved_name, identifier) = self
+            .resolve(&full_name)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown identifier '{name}'.")))?;
+
+        match identifier.type_.as_deref() {
+            Some("function") | Some("label") => identifier.pc.ok_or_else(|| {
+                PyValueError::new_err(format!("Identifier '{resolved_name}' has no pc."))
+            }),
+            type_ => Err(PyTypeError::new_err(format!(
+                "Expected '{resolved_name}' to be a function, found {}.",
+                type_.unwrap_or("an identifier without type")
+            ))),
+        }
+    }
+
+    /// Looks up `full_name`, replacing any alias found in the name (or in one of its prefixes)
+    /// by its destination.
+    fn resolve(&self, full_name: &str) -> Option<(String, &IdentifierInfo)> {
+        let mut current = full_name.to_string();
+        for _ in 0..MAX_ALIAS_DEPTH {
+            match self.identifiers.get(&current) {
+                Some(IdentifierInfo {
+                    type_: Some(type_),
+                    destination: Some(destination),
+                    ..
+                }) if type_ == "alias" => current = destination.clone(),
+                Some(identifier) => return Some((current, identifier)),
+                None => current = self.resolve_prefix_alias(&current)?,
+            }
+        }
+        None
+    }
+
+    /// Finds the longest prefix of `name` which is an alias and substitutes it.
+    fn resolve_prefix_alias(&self, name: &str) -> Option<String> {
+        let mut prefix_end = name.len();
+        while let Some(dot) = name[..prefix_end].rfind('.') {
+            let (prefix, suffix) = (&name[..dot], &name[dot..]);
+            if let Some(IdentifierInfo {
+                type_: Some(type_),
+                destination: Some(destination),
+                ..
+            }) = self.identifiers.get(prefix)
+            {
+                if type_ == "alias" {
+                    return Some(format!("{destination}{suffix}"));
+                }
+            }
+            prefix_end = dot;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn identifier_manager() -> IdentifierManager {
+        IdentifierManager::from_program_json(
+            r#"{
+                "identifiers": {
+                    "__main__.main": {"type": "function", "pc": 4, "decorators": []},
+                    "__main__.not_main": {"type": "function", "pc": 0, "decorators": []},
+                    "__main__.loop": {"type": "label", "pc": 2},
+                    "__main__.SIZE": {"type": "const", "value": 3},
+                    "__main__.alloc": {"type": "alias", "destination": "lib.alloc.alloc"},
+                    "__main__.lib_alias": {"type": "alias", "destination": "lib.alloc"},
+                    "__main__.cycle": {"type": "alias", "destination": "__main__.cycle"},
+                    "lib.alloc.alloc": {"type": "function", "pc": 10, "decorators": []}
+                }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn get_label_relative_name() {
+        assert_eq!(
+            identifier_manager().get_label("not_main", false).unwrap(),
+            0
+        );
+        assert_eq!(identifier_manager().get_label("loop", false).unwrap(), 2);
+    }
+
+    #[test]
+    fn get_label_full_name() {
+        let identifiers = identifier_manager();
+        assert_eq!(identifiers.get_label("__main__.main", true).unwrap(), 4);
+        assert_eq!(identifiers.get_label("lib.alloc.alloc", true).unwrap(), 10);
+        assert!(identifiers.get_label("main", true).is_err());
+    }
+
+    #[test]
+    fn get_label_alias() {
+        let identifiers = identifier_manager();
+        assert_eq!(identifiers.get_label("alloc", false).unwrap(), 10);
+        assert_eq!(identifiers.get_label("lib_alias.alloc", false).unwrap(), 10);
+    }
+
+    #[test]
+    fn get_label_missing_identifier() {
+        let result = identifier_manager().get_label("missing", false);
+        assert_eq!(
+            format!("{:?}", result),
+            format!(
+                "{:?}",
+                Err::<usize, PyErr>(PyValueError::new_err("Unknown identifier 'missing'."))
+            )
+        );
+        assert!(identifier_manager().get_label("cycle", false).is_err());
+    }
+
+    #[test]
+    fn get_label_not_a_function() {
+        let result = identifier_manager().get_label("SIZE", false);
+        assert_eq!(
+            format!("{:?}", result),
+            format!(
+                "{:?}",
+                Err::<usize, PyErr>(PyTypeError::new_err(
+                    "Expected '__main__.SIZE' to be a function, found const."
+                ))
+            )
+        );
+    }
+}
@@ -1,9 +1,9 @@
 use crate::utils::const_path_to_const_name;
 use num_bigint::BigInt;
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyIndexError, PyTypeError, PyValueError};
 use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet},
+    cell::{Cell, RefCell},
+    collections::HashMap,
     rc::Rc,
 };
 
@@ -14,23 +14,29 @@ use cairo_rs::{
         hint_processor_utils::compute_addr_from_reference as cairo_rs_compute_addr_from_reference,
     },
     serde::deserialize_program::{ApTracking, Member},
-    types::relocatable::Relocatable,
+    types::relocatable::{MaybeRelocatable, Relocatable},
     vm::{errors::vm_errors::VirtualMachineError, vm_core::VirtualMachine},
 };
 use pyo3::{
-    exceptions::PyAttributeError, pyclass, pymethods, IntoPy, PyObject, PyResult, Python,
-    ToPyObject,
+    exceptions::PyAttributeError,
+    pyclass, pymethods,
+    types::{PyDict, PyTuple},
+    IntoPy, PyAny, PyObject, PyRef, PyResult, Python, ToPyObject,
 };
 
-use crate::{relocatable::PyMaybeRelocatable, vm_core::PyVM};
+use crate::{
+    memory::{check_not_frozen, write_memory},
+    relocatable::{PyMaybeRelocatable, PyRelocatable},
+    vm_core::PyVM,
+};
 
 const IDS_GET_ERROR_MSG: &str = "Failed to get ids value";
 const IDS_SET_ERROR_MSG: &str = "Failed to set ids value to Cairo memory";
-const STRUCT_TYPES_GET_ERROR_MSG: &str = "Failed to get struct type";
 
 #[pyclass(unsendable)]
 pub struct PyIds {
     vm: Rc<RefCell<VirtualMachine>>,
+    memory_frozen: Rc<Cell<bool>>,
     references: HashMap<String, HintReference>,
     ap_tracking: ApTracking,
     constants: HashMap<String, BigInt>,
@@ -47,34 +53,8 @@ impl PyIds {
 
         // Support for for ids.{Struct Definition} information
         // Example: ids.DictAccess
-        let mut types_set = HashSet::new();
-        for key in self.struct_types.keys() {
-            types_set.insert(
-                key.rsplit('.')
-                    .next()
-                    .ok_or_else(|| PyValueError::new_err(STRUCT_TYPES_GET_ERROR_MSG))?,
-            );
-        }
-        if types_set.contains(name) {
-            let mut structs_size = HashMap::new();
-
-            for (key, v) in self.struct_types.iter() {
-                let max_member = v.values().max_by(|x, y| x.offset.cmp(&y.offset));
-
-                let max_offset = match max_member {
-                    Some(member) => member.offset + 1,
-                    _ => 0,
-                };
-                structs_size.insert(
-                    key.rsplit('.')
-                        .next()
-                        .ok_or_else(|| PyValueError::new_err(STRUCT_TYPES_GET_ERROR_MSG))?,
-                    max_offset,
-                );
-            }
-            if let Some(size) = structs_size.get(name) {
-                return Ok(CairoStruct { SIZE: *size }.into_py(py));
-            }
+        if let Some(full_name) = resolve_struct_name(&self.struct_types, name)? {
+            return Ok(self.struct_definition(full_name).into_py(py));
         }
 
         let hint_ref = self
@@ -83,43 +63,18 @@ impl PyIds {
             .ok_or_else(|| PyValueError::new_err(IDS_GET_ERROR_MSG))?;
 
         if let Some(cairo_type) = hint_ref.cairo_type.as_deref() {
-            let chars = cairo_type.chars().rev();
-            let clear_ref = chars
-                .skip_while(|c| c == &'*')
-                .collect::<String>()
-                .chars()
-                .rev()
-                .collect::<String>();
-
-            if self.struct_types.contains_key(cairo_type) {
-                return Ok(PyTypedId {
-                    vm: self.vm.clone(),
-                    hint_value: compute_addr_from_reference(
-                        hint_ref,
-                        &self.vm.borrow(),
-                        &self.ap_tracking,
-                    )?,
-                    cairo_type: cairo_type.to_string(),
-                    struct_types: Rc::clone(&self.struct_types),
-                }
-                .into_py(py));
-            } else if self.struct_types.contains_key(&clear_ref) {
+            let clear_ref = cairo_type.trim_end_matches('*');
+            if type_members(&self.struct_types, clear_ref).is_some() {
                 let addr =
                     compute_addr_from_reference(hint_ref, &self.vm.borrow(), &self.ap_tracking)?;
-
-                let hint_value = self
-                    .vm
-                    .borrow()
-                    .get_relocatable(&addr)
-                    .map_err(|err| PyValueError::new_err(err.to_string()))?;
-
-                return Ok(PyTypedId {
-                    vm: self.vm.clone(),
-                    hint_value,
-                    cairo_type: clear_ref,
-                    struct_types: Rc::clone(&self.struct_types),
-                }
-                .into_py(py));
+                return typed_value(
+                    py,
+                    &self.vm,
+                    &self.memory_frozen,
+                    &self.struct_types,
+                    cairo_type,
+                    addr,
+                );
             }
         }
 
@@ -130,16 +85,23 @@ impl PyIds {
         )
     }
 
-    pub fn __setattr__(&self, name: &str, val: PyMaybeRelocatable) -> PyResult<()> {
+    pub fn __setattr__(&self, name: &str, val: &PyAny) -> PyResult<()> {
         let hint_ref = self
             .references
             .get(name)
             .ok_or_else(|| PyValueError::new_err(IDS_SET_ERROR_MSG))?;
         let var_addr = compute_addr_from_reference(hint_ref, &self.vm.borrow(), &self.ap_tracking)?;
-        self.vm
-            .borrow_mut()
-            .insert_value(&var_addr, &val)
-            .map_err(|err| PyValueError::new_err(err.to_string()))
+        let cairo_type = hint_ref.cairo_type.as_deref().unwrap_or("felt");
+        let values = struct_values(&self.struct_types, cairo_type, val)?;
+        write_values(&self.vm, &self.memory_frozen, var_addr, values)
+    }
+
+    /// Returns the definition of a struct given its full name, such as `__main__.MyStruct`, or its
+    /// short name when no other struct has it.
+    pub fn get_struct(&self, name: &str) -> PyResult<CairoStruct> {
+        let full_name = resolve_struct_name(&self.struct_types, name)?
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown struct '{name}'")))?;
+        Ok(self.struct_definition(full_name))
     }
 }
 
@@ -153,24 +115,76 @@ impl PyIds {
     ) -> PyIds {
         PyIds {
             vm: vm.get_vm(),
+            memory_frozen: Rc::clone(&vm.memory_frozen),
             references: references.clone(),
             ap_tracking: ap_tracking.clone(),
             constants: const_path_to_const_name(constants),
             struct_types,
         }
     }
+
+    fn struct_definition(&self, full_name: &str) -> CairoStruct {
+        CairoStruct {
+            full_name: full_name.to_string(),
+            SIZE: type_size(&self.struct_types, full_name),
+            members: self.struct_types[full_name]
+                .iter()
+                .map(|(name, member)| {
+                    (
+                        name.clone(),
+                        PyMemberDefinition {
+                            offset: member.offset,
+                            cairo_type: member.cairo_type.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
 }
 
+/// The definition of a struct, as `ids.MyStruct`. The offset of a member is also available as
+/// an attribute of its name, as in `ids.DictAccess.new_value`.
 #[allow(non_snake_case)]
 #[pyclass(unsendable)]
-struct CairoStruct {
+pub struct CairoStruct {
+    #[pyo3(get)]
+    full_name: String,
     #[pyo3(get)]
     SIZE: usize,
+    #[pyo3(get)]
+    members: HashMap<String, PyMemberDefinition>,
+}
+
+#[pymethods]
+impl CairoStruct {
+    #[getter]
+    fn __getattr__(&self, name: &str) -> PyResult<usize> {
+        self.members
+            .get(name)
+            .map(|member| member.offset)
+            .ok_or_else(|| {
+                PyAttributeError::new_err(format!(
+                    "Struct {} has no member '{name}'",
+                    self.full_name
+                ))
+            })
+    }
+}
+
+#[pyclass(unsendable, name = "MemberDefinition")]
+#[derive(Clone)]
+struct PyMemberDefinition {
+    #[pyo3(get)]
+    offset: usize,
+    #[pyo3(get)]
+    cairo_type: String,
 }
 
 #[pyclass(unsendable)]
 struct PyTypedId {
     vm: Rc<RefCell<VirtualMachine>>,
+    memory_frozen: Rc<Cell<bool>>,
     hint_value: Relocatable,
     cairo_type: String,
     struct_types: Rc<HashMap<String, HashMap<String, Member>>>,
@@ -183,59 +197,440 @@ impl PyTypedId {
         if name == "address_" {
             return Ok(PyMaybeRelocatable::from(self.hint_value).to_object(py));
         }
-        let struct_type = self.struct_types.get(&self.cairo_type).unwrap();
-
-        match struct_type.get(name) {
-            Some(member) => {
-                let vm = self.vm.borrow();
-                Ok(match member.cairo_type.as_str() {
-                    "felt" | "felt*" => vm
-                        .get_maybe(&(self.hint_value + member.offset))
-                        .map_err(|err| PyValueError::new_err(err.to_string()))?
-                        .map(|x| PyMaybeRelocatable::from(x).to_object(py))
-                        .unwrap_or_else(|| py.None()),
-
-                    cairo_type => PyTypedId {
-                        vm: self.vm.clone(),
-                        hint_value: (self.hint_value + member.offset),
-                        cairo_type: cairo_type.to_string(),
-                        struct_types: self.struct_types.clone(),
-                    }
-                    .into_py(py),
-                })
-            }
-            None => Err(PyAttributeError::new_err(format!(
-                "'PyTypeId' object has no attribute '{}'",
-                name
-            ))),
+        let member = self.member(name)?;
+        typed_value(
+            py,
+            &self.vm,
+            &self.memory_frozen,
+            &self.struct_types,
+            &member.cairo_type,
+            self.hint_value + member.offset,
+        )
+    }
+
+    /// Follows a pointer, as `ids.ptr` does for `T*` references: for a `T**` reference, `ids.ptr`
+    /// is the `T*` it points to, and `ids.ptr.deref()` the `T` this one points to.
+    pub fn deref(&self, py: Python) -> PyResult<PyObject> {
+        if !self.cairo_type.ends_with('*') {
+            return Err(PyTypeError::new_err(format!(
+                "Can't dereference a value of type {}",
+                self.cairo_type
+            )));
         }
+        typed_value(
+            py,
+            &self.vm,
+            &self.memory_frozen,
+            &self.struct_types,
+            &self.cairo_type,
+            self.hint_value,
+        )
     }
 
-    pub fn __setattr__(&self, field_name: &str, val: PyMaybeRelocatable) -> PyResult<()> {
-        let struct_type = self
-            .struct_types
-            .get(&self.cairo_type)
-            .ok_or_else(|| PyValueError::new_err(STRUCT_TYPES_GET_ERROR_MSG))?;
+    /// For tuples, returns the member at `index`. Otherwise, returns the element at `index` of the
+    /// array that starts at this typed id, so that `ids.ptr[i]` is the i-th struct pointed to by
+    /// `ptr`.
+    pub fn __getitem__(&self, py: Python, index: usize) -> PyResult<PyObject> {
+        if let Some(member) = self.tuple_member(index)? {
+            return typed_value(
+                py,
+                &self.vm,
+                &self.memory_frozen,
+                &self.struct_types,
+                &member.cairo_type,
+                self.hint_value + member.offset,
+            );
+        }
+        Ok(PyTypedId {
+            vm: Rc::clone(&self.vm),
+            memory_frozen: Rc::clone(&self.memory_frozen),
+            hint_value: self.element_addr(index),
+            cairo_type: self.cairo_type.clone(),
+            struct_types: Rc::clone(&self.struct_types),
+        }
+        .into_py(py))
+    }
 
-        let member = struct_type.get(field_name).ok_or_else(|| {
+    pub fn __setitem__(&self, index: usize, val: &PyAny) -> PyResult<()> {
+        let (cairo_type, addr) = match self.tuple_member(index)? {
+            Some(member) => (member.cairo_type, self.hint_value + member.offset),
+            None => (self.cairo_type.clone(), self.element_addr(index)),
+        };
+        let values = struct_values(&self.struct_types, &cairo_type, val)?;
+        write_values(&self.vm, &self.memory_frozen, addr, values)
+    }
+
+    pub fn __setattr__(&self, field_name: &str, val: &PyAny) -> PyResult<()> {
+        let member = self.member(field_name)?;
+        let values = struct_values(&self.struct_types, &member.cairo_type, val)?;
+        write_values(
+            &self.vm,
+            &self.memory_frozen,
+            self.hint_value + member.offset,
+            values,
+        )
+    }
+}
+
+impl PyTypedId {
+    fn member(&self, name: &str) -> PyResult<Member> {
+        let members = type_members(&self.struct_types, &self.cairo_type).ok_or_else(|| {
             PyAttributeError::new_err(format!(
-                "'PyTypeId' object has no attribute '{}'",
-                field_name
+                "'{}' is a pointer, use deref() to access its members",
+                self.cairo_type
             ))
         })?;
+        members
+            .into_iter()
+            .find(|(member_name, _)| member_name.as_deref() == Some(name))
+            .map(|(_, member)| member)
+            .ok_or_else(|| {
+                PyAttributeError::new_err(format!("'PyTypeId' object has no attribute '{}'", name))
+            })
+    }
 
-        let mut vm = self.vm.borrow_mut();
-        match member.cairo_type.as_str() {
-            "felt" | "felt*" => {
-                let field_addr = self.hint_value + member.offset;
-                vm.insert_value(&field_addr, val).map_err(|err| PyValueError::new_err(err.to_string()))
+    /// Returns the member at `index` if this typed id is a tuple.
+    fn tuple_member(&self, index: usize) -> PyResult<Option<Member>> {
+        if !is_tuple_type(&self.cairo_type) {
+            return Ok(None);
+        }
+        let mut members = type_members(&self.struct_types, &self.cairo_type).unwrap_or_default();
+        if index >= members.len() {
+            return Err(PyIndexError::new_err(format!(
+                "Tuple {} has no member at index {index}",
+                self.cairo_type
+            )));
+        }
+        Ok(Some(members.swap_remove(index).1))
+    }
+
+    fn element_addr(&self, index: usize) -> Relocatable {
+        self.hint_value + index * type_size(&self.struct_types, &self.cairo_type)
+    }
+}
+
+/// `cast(addr, "MyStruct*")` in the hints: a typed id of the given struct or tuple type at `addr`,
+/// as `cast(addr, MyStruct*)` in Cairo. Struct names are resolved as in `ids.MyStruct`.
+#[pyclass(unsendable, name = "Cast")]
+pub struct PyCast {
+    vm: Rc<RefCell<VirtualMachine>>,
+    memory_frozen: Rc<Cell<bool>>,
+    struct_types: Rc<HashMap<String, HashMap<String, Member>>>,
+}
+
+#[pymethods]
+impl PyCast {
+    fn __call__(&self, py: Python, addr: &PyRelocatable, cairo_type: &str) -> PyResult<PyObject> {
+        let pointee = cairo_type.trim_end_matches('*');
+        let pointers = &cairo_type[pointee.len()..];
+        let pointee = if is_tuple_type(pointee) {
+            pointee
+        } else {
+            resolve_struct_name(&self.struct_types, pointee)?.ok_or_else(|| {
+                PyTypeError::new_err(format!(
+                    "Can only cast to struct and tuple types, got {cairo_type}"
+                ))
+            })?
+        };
+        // As for ids, a pointer is represented by the value it points to
+        let cairo_type = format!("{pointee}{pointers}");
+        Ok(PyTypedId {
+            vm: Rc::clone(&self.vm),
+            memory_frozen: Rc::clone(&self.memory_frozen),
+            hint_value: Relocatable::from(addr),
+            cairo_type: cairo_type
+                .strip_suffix('*')
+                .unwrap_or(&cairo_type)
+                .to_string(),
+            struct_types: Rc::clone(&self.struct_types),
+        }
+        .into_py(py))
+    }
+}
+
+impl PyCast {
+    pub fn new(vm: &PyVM, struct_types: Rc<HashMap<String, HashMap<String, Member>>>) -> PyCast {
+        PyCast {
+            vm: vm.get_vm(),
+            memory_frozen: Rc::clone(&vm.memory_frozen),
+            struct_types,
+        }
+    }
+}
+
+/// Returns the full name of the struct `name` refers to. Short names that more than one struct
+/// has resolve to the one in `__main__`, if any.
+fn resolve_struct_name<'a>(
+    struct_types: &'a HashMap<String, HashMap<String, Member>>,
+    name: &str,
+) -> PyResult<Option<&'a str>> {
+    if let Some((full_name, _)) = struct_types.get_key_value(name) {
+        return Ok(Some(full_name));
+    }
+    let mut candidates = struct_types
+        .keys()
+        .filter(|full_name| full_name.rsplit('.').next() == Some(name))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    match candidates.len() {
+        0 => Ok(None),
+        1 => Ok(candidates.pop()),
+        _ => {
+            let main_name = format!("__main__.{name}");
+            if let Some(full_name) = candidates.iter().find(|c| **c == main_name) {
+                return Ok(Some(*full_name));
             }
+            candidates.sort_unstable();
+            Err(PyValueError::new_err(format!(
+                "Struct name '{name}' is ambiguous, use one of: {}",
+                candidates.join(", ")
+            )))
+        }
+    }
+}
+
+/// Returns the value of the variable of type `cairo_type` at `addr`: a typed id for structs and
+/// tuples, a typed id of the pointee for pointers to them, and the value in memory otherwise.
+fn typed_value(
+    py: Python,
+    vm: &Rc<RefCell<VirtualMachine>>,
+    memory_frozen: &Rc<Cell<bool>>,
+    struct_types: &Rc<HashMap<String, HashMap<String, Member>>>,
+    cairo_type: &str,
+    addr: Relocatable,
+) -> PyResult<PyObject> {
+    if type_members(struct_types, cairo_type).is_some() {
+        return Ok(PyTypedId {
+            vm: Rc::clone(vm),
+            memory_frozen: Rc::clone(memory_frozen),
+            hint_value: addr,
+            cairo_type: cairo_type.to_string(),
+            struct_types: Rc::clone(struct_types),
+        }
+        .into_py(py));
+    }
 
-            _cairo_type => Err(PyValueError::new_err("Error: It should be possible to assign a struct into another struct's field. See issue #86")),
+    match cairo_type.strip_suffix('*') {
+        Some(pointee) if type_members(struct_types, pointee.trim_end_matches('*')).is_some() => {
+            let hint_value = vm
+                .borrow()
+                .get_relocatable(&addr)
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            Ok(PyTypedId {
+                vm: Rc::clone(vm),
+                memory_frozen: Rc::clone(memory_frozen),
+                hint_value,
+                cairo_type: pointee.to_string(),
+                struct_types: Rc::clone(struct_types),
+            }
+            .into_py(py))
         }
+        _ => Ok(vm
+            .borrow()
+            .get_maybe(&addr)
+            .map_err(|err| PyValueError::new_err(err.to_string()))?
+            .map(|x| PyMaybeRelocatable::from(x).to_object(py))
+            .unwrap_or_else(|| py.None())),
     }
 }
 
+fn is_tuple_type(cairo_type: &str) -> bool {
+    cairo_type.starts_with('(') && cairo_type.ends_with(')')
+}
+
+/// Returns the members of a struct or tuple type sorted by offset, or None for any other type.
+/// Only the members of structs and named tuples, such as `(x: felt, y: felt)`, have names.
+fn type_members(
+    struct_types: &HashMap<String, HashMap<String, Member>>,
+    cairo_type: &str,
+) -> Option<Vec<(Option<String>, Member)>> {
+    if let Some(struct_type) = struct_types.get(cairo_type) {
+        let mut members = struct_type
+            .iter()
+            .map(|(name, member)| (Some(name.clone()), member.clone()))
+            .collect::<Vec<_>>();
+        members.sort_by_key(|(_, member)| member.offset);
+        return Some(members);
+    }
+    if !is_tuple_type(cairo_type) {
+        return None;
+    }
+
+    let mut members = Vec::new();
+    let mut offset = 0;
+    for item in split_tuple_items(&cairo_type[1..cairo_type.len() - 1]) {
+        let (name, item_type) = match item.split_once(':') {
+            Some((name, item_type)) if !name.contains('(') => {
+                (Some(name.trim().to_string()), item_type.trim())
+            }
+            _ => (None, item),
+        };
+        members.push((
+            name,
+            Member {
+                cairo_type: item_type.to_string(),
+                offset,
+            },
+        ));
+        offset += type_size(struct_types, item_type);
+    }
+    Some(members)
+}
+
+/// Splits the items of a tuple type on the commas that aren't in a nested tuple.
+fn split_tuple_items(items: &str) -> Vec<&str> {
+    let mut split = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in items.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(items[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    split.push(items[start..].trim());
+    // A tuple of a single item is written with a trailing comma
+    split.retain(|item| !item.is_empty());
+    split
+}
+
+/// Number of memory cells taken by a value of type `cairo_type`: structs and tuples take the
+/// space of their members, anything else a single cell.
+fn type_size(struct_types: &HashMap<String, HashMap<String, Member>>, cairo_type: &str) -> usize {
+    match type_members(struct_types, cairo_type) {
+        Some(members) => members
+            .iter()
+            .map(|(_, member)| member.offset + type_size(struct_types, &member.cairo_type))
+            .max()
+            .unwrap_or(0),
+        None => 1,
+    }
+}
+
+/// Returns the values to write, by offset, to assign `value` to a variable of type `cairo_type`.
+/// Structs and tuples can be assigned a typed id of the same type, or a tuple or dict of the
+/// values of their members, converted in the same way. Any other type takes a single felt or
+/// relocatable.
+fn struct_values(
+    struct_types: &HashMap<String, HashMap<String, Member>>,
+    cairo_type: &str,
+    value: &PyAny,
+) -> PyResult<Vec<(usize, MaybeRelocatable)>> {
+    let members = match type_members(struct_types, cairo_type) {
+        Some(members) => members,
+        None => {
+            let value = value.extract::<PyMaybeRelocatable>()?;
+            return Ok(vec![(0, MaybeRelocatable::from(value))]);
+        }
+    };
+
+    if let Ok(typed_id) = value.extract::<PyRef<PyTypedId>>() {
+        if typed_id.cairo_type != cairo_type {
+            return Err(PyTypeError::new_err(format!(
+                "Can't assign a value of type {} to a variable of type {cairo_type}",
+                typed_id.cairo_type
+            )));
+        }
+        let vm = typed_id.vm.borrow();
+        return Ok(read_struct_values(
+            &vm,
+            struct_types,
+            cairo_type,
+            typed_id.hint_value,
+        ));
+    }
+
+    let member_values = if let Ok(tuple) = value.downcast::<PyTuple>() {
+        if tuple.len() != members.len() {
+            return Err(PyValueError::new_err(format!(
+                "Type {cairo_type} has {} members, got a tuple of {} values",
+                members.len(),
+                tuple.len()
+            )));
+        }
+        members
+            .into_iter()
+            .map(|(_, member)| member)
+            .zip(tuple.iter())
+            .collect::<Vec<_>>()
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        dict.iter()
+            .map(|(name, value)| {
+                let name = name.extract::<&str>()?;
+                let member = members
+                    .iter()
+                    .find(|(member_name, _)| member_name.as_deref() == Some(name))
+                    .ok_or_else(|| {
+                        PyValueError::new_err(format!("Type {cairo_type} has no member '{name}'"))
+                    })?;
+                Ok((member.1.clone(), value))
+            })
+            .collect::<PyResult<Vec<_>>>()?
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "Can't assign a value of type {} to a variable of type {cairo_type}",
+            value.get_type().name()?
+        )));
+    };
+
+    let mut values = Vec::new();
+    for (member, value) in member_values {
+        let member_values = struct_values(struct_types, &member.cairo_type, value)?;
+        values.extend(
+            member_values
+                .into_iter()
+                .map(|(offset, value)| (member.offset + offset, value)),
+        );
+    }
+    Ok(values)
+}
+
+/// Reads the values of the struct or tuple of type `cairo_type` at `addr`, skipping the unknown
+/// ones.
+fn read_struct_values(
+    vm: &VirtualMachine,
+    struct_types: &HashMap<String, HashMap<String, Member>>,
+    cairo_type: &str,
+    addr: Relocatable,
+) -> Vec<(usize, MaybeRelocatable)> {
+    let members = match type_members(struct_types, cairo_type) {
+        Some(members) => members,
+        None => {
+            return match vm.get_maybe(&addr) {
+                Ok(Some(value)) => vec![(0, value)],
+                _ => Vec::new(),
+            }
+        }
+    };
+    members
+        .into_iter()
+        .flat_map(|(_, member)| {
+            read_struct_values(vm, struct_types, &member.cairo_type, addr + member.offset)
+                .into_iter()
+                .map(move |(offset, value)| (member.offset + offset, value))
+        })
+        .collect()
+}
+
+fn write_values(
+    vm: &RefCell<VirtualMachine>,
+    memory_frozen: &Cell<bool>,
+    addr: Relocatable,
+    values: Vec<(usize, MaybeRelocatable)>,
+) -> PyResult<()> {
+    check_not_frozen(memory_frozen)?;
+    let mut vm = vm.borrow_mut();
+    for (offset, value) in values {
+        write_memory(|| vm.insert_value(&(addr + offset), value))?;
+    }
+    Ok(())
+}
+
 ///Returns the Value given by a reference as an Option<MaybeRelocatable>
 pub fn get_value_from_reference(
     vm: &VirtualMachine,
@@ -260,6 +655,23 @@ pub fn get_value_from_reference(
         .map(PyMaybeRelocatable::from)
 }
 
+/// Returns the cells of the ids variables of a hint. Those that can't be computed are left out.
+pub(crate) fn ids_cells(
+    references: &HashMap<String, HintReference>,
+    vm: &VirtualMachine,
+    ap_tracking: &ApTracking,
+    struct_types: &HashMap<String, HashMap<String, Member>>,
+) -> Vec<Relocatable> {
+    let mut cells = Vec::new();
+    for hint_ref in references.values() {
+        if let Ok(addr) = compute_addr_from_reference(hint_ref, vm, ap_tracking) {
+            let cairo_type = hint_ref.cairo_type.as_deref().unwrap_or("felt");
+            cells.extend((0..type_size(struct_types, cairo_type)).map(|offset| addr + offset));
+        }
+    }
+    cells
+}
+
 ///Computes the memory address of the ids variable indicated by the HintReference as a Relocatable
 pub fn compute_addr_from_reference(
     //Reference data of the ids variable
@@ -273,7 +685,10 @@ pub fn compute_addr_from_reference(
 }
 #[cfg(test)]
 mod tests {
-    use crate::{memory::PyMemory, relocatable::PyRelocatable};
+    use crate::{
+        memory::{FrozenMemoryError, PyMemory},
+        relocatable::PyRelocatable,
+    };
     use cairo_rs::{
         bigint,
         types::{instruction::Register, relocatable::MaybeRelocatable},
@@ -581,6 +996,80 @@ memory[fp + 1] = ids.ns.struct.address_
         });
     }
 
+    #[test]
+    fn ids_struct_definitions() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            let (_, simple_struct) = create_simple_struct_type();
+            let struct_types = HashMap::from([
+                (
+                    String::from("__main__.Nested"),
+                    HashMap::from([
+                        (
+                            String::from("x"),
+                            Member {
+                                cairo_type: String::from("felt"),
+                                offset: 0,
+                            },
+                        ),
+                        (
+                            String::from("inner"),
+                            Member {
+                                cairo_type: String::from("__main__.SimpleStruct"),
+                                offset: 1,
+                            },
+                        ),
+                    ]),
+                ),
+                (String::from("__main__.SimpleStruct"), simple_struct.clone()),
+                (String::from("other.SimpleStruct"), simple_struct),
+                (String::from("a.Dup"), HashMap::new()),
+                (String::from("b.Dup"), HashMap::new()),
+            ]);
+
+            let ids = PyIds::new(
+                &vm,
+                &HashMap::new(),
+                &ApTracking::default(),
+                &HashMap::new(),
+                Rc::new(struct_types),
+            );
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("ids", PyCell::new(py, ids).unwrap())
+                .unwrap();
+
+            let code = r#"
+assert ids.Nested.SIZE == 3
+assert ids.Nested.inner == 1
+assert ids.Nested.members["inner"].cairo_type == "__main__.SimpleStruct"
+assert ids.Nested.members["inner"].offset == 1
+assert ids.SimpleStruct.full_name == "__main__.SimpleStruct"
+assert ids.get_struct("other.SimpleStruct").full_name == "other.SimpleStruct"
+assert ids.get_struct("b.Dup").SIZE == 0
+"#;
+
+            let py_result = py.run(code, Some(globals), None);
+
+            assert!(py_result.is_ok());
+
+            //Dup is defined in two modules other than __main__
+            let py_result = py.run("ids.Dup", Some(globals), None);
+            assert!(py_result.unwrap_err().is_instance_of::<PyValueError>(py));
+
+            //Nested has no member y
+            let py_result = py.run("ids.Nested.y", Some(globals), None);
+            assert!(py_result
+                .unwrap_err()
+                .is_instance_of::<PyAttributeError>(py));
+        });
+    }
+
     #[test]
     fn ids_get_from_pointer() {
         Python::with_gil(|py| {
@@ -839,6 +1328,356 @@ ids.struct.ptr = ids.fp
         });
     }
 
+    #[test]
+    fn ids_set_frozen_memory() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            for _ in 0..2 {
+                vm.vm.borrow_mut().add_memory_segment();
+            }
+            //Create references
+            let mut references = HashMap::new();
+            references.insert(String::from("a"), HintReference::new_simple(2));
+            references.insert(
+                String::from("struct"),
+                HintReference {
+                    offset1: OffsetValue::Reference(Register::FP, 0, false),
+                    offset2: OffsetValue::Value(0),
+                    dereference: true,
+                    ap_tracking_data: None,
+                    cairo_type: Some(String::from("SimpleStruct")),
+                },
+            );
+
+            let struct_types = HashMap::from([create_simple_struct_type()]);
+
+            let ids = PyIds::new(
+                &vm,
+                &references,
+                &ApTracking::default(),
+                &HashMap::new(),
+                Rc::new(struct_types),
+            );
+            PyMemory::new(&vm).freeze();
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("ids", PyCell::new(py, ids).unwrap())
+                .unwrap();
+
+            for code in ["ids.a = 1", "ids.struct.x = 5"] {
+                let error = py.run(code, Some(globals), None).unwrap_err();
+                assert!(error.is_instance_of::<FrozenMemoryError>(py));
+            }
+            assert_eq!(
+                vm.vm.borrow().get_maybe(&Relocatable::from((1, 0))),
+                Ok(None)
+            );
+            assert_eq!(
+                vm.vm.borrow().get_maybe(&Relocatable::from((1, 2))),
+                Ok(None)
+            );
+        });
+    }
+
+    #[test]
+    fn ids_set_whole_struct() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            for _ in 0..2 {
+                vm.vm.borrow_mut().add_memory_segment();
+            }
+            //Create references to three Pair structs at fp, fp + 3 and fp + 6
+            let mut references = HashMap::new();
+            for (name, offset) in [("p", 0), ("q", 3), ("r", 6)] {
+                references.insert(
+                    String::from(name),
+                    HintReference {
+                        offset1: OffsetValue::Reference(Register::FP, offset, false),
+                        offset2: OffsetValue::Value(0),
+                        dereference: true,
+                        ap_tracking_data: None,
+                        cairo_type: Some(String::from("Pair")),
+                    },
+                );
+            }
+
+            //Insert new type Pair { a: felt, s: SimpleStruct }
+            let struct_types = HashMap::from([
+                create_simple_struct_type(),
+                (
+                    String::from("Pair"),
+                    HashMap::from([
+                        (
+                            String::from("a"),
+                            Member {
+                                cairo_type: String::from("felt"),
+                                offset: 0,
+                            },
+                        ),
+                        (
+                            String::from("s"),
+                            Member {
+                                cairo_type: String::from("SimpleStruct"),
+                                offset: 1,
+                            },
+                        ),
+                    ]),
+                ),
+            ]);
+
+            let ids = PyIds::new(
+                &vm,
+                &references,
+                &ApTracking::default(),
+                &HashMap::new(),
+                Rc::new(struct_types),
+            );
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("ids", PyCell::new(py, ids).unwrap())
+                .unwrap();
+
+            let code = r#"
+ids.p = (1, (2, 3))
+ids.q = ids.p
+ids.r = {"a": 4}
+ids.r.s = {"x": 5}
+"#;
+
+            let py_result = py.run(code, Some(globals), None);
+
+            assert!(py_result.is_ok());
+            let memory = vm.vm.borrow();
+            for (offset, value) in [
+                (0, 1),
+                (1, 2),
+                (2, 3),
+                (3, 1),
+                (4, 2),
+                (5, 3),
+                (6, 4),
+                (7, 5),
+            ] {
+                assert_eq!(
+                    memory.get_maybe(&Relocatable::from((1, offset))),
+                    Ok(Some(MaybeRelocatable::from(bigint!(value))))
+                );
+            }
+            assert_eq!(memory.get_maybe(&Relocatable::from((1, 8))), Ok(None));
+            drop(memory);
+
+            //Wrong number of members
+            let py_result = py.run("ids.r.s = (1,)", Some(globals), None);
+            assert!(py_result.unwrap_err().is_instance_of::<PyValueError>(py));
+
+            //Wrong struct type
+            let py_result = py.run("ids.r.s = ids.p", Some(globals), None);
+            assert!(py_result.unwrap_err().is_instance_of::<PyTypeError>(py));
+
+            //Unknown member
+            let py_result = py.run("ids.r = {\"b\": 1}", Some(globals), None);
+            assert!(py_result.is_err());
+        });
+    }
+
+    #[test]
+    fn ids_pointer_indexing() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            for _ in 0..3 {
+                vm.vm.borrow_mut().add_memory_segment();
+            }
+            //Create references
+            let mut references = HashMap::new();
+            //Insert SimpleStruct pointer
+            references.insert(
+                String::from("ssp"),
+                HintReference {
+                    offset1: OffsetValue::Reference(Register::FP, 0, false),
+                    offset2: OffsetValue::Value(0),
+                    dereference: true,
+                    ap_tracking_data: None,
+                    cairo_type: Some(String::from("SimpleStruct*")),
+                },
+            );
+            //Insert pointer to the SimpleStruct pointer
+            references.insert(
+                String::from("sspp"),
+                HintReference {
+                    offset1: OffsetValue::Reference(Register::FP, 1, false),
+                    offset2: OffsetValue::Value(0),
+                    dereference: true,
+                    ap_tracking_data: None,
+                    cairo_type: Some(String::from("SimpleStruct**")),
+                },
+            );
+
+            //Insert ids.ssp and ids.sspp into memory
+            vm.vm
+                .borrow_mut()
+                .insert_value(&Relocatable::from((1, 0)), &MaybeRelocatable::from((2, 0)))
+                .unwrap();
+            vm.vm
+                .borrow_mut()
+                .insert_value(&Relocatable::from((1, 1)), &MaybeRelocatable::from((1, 0)))
+                .unwrap();
+
+            let struct_types = HashMap::from([create_simple_struct_type()]);
+
+            let ids = PyIds::new(
+                &vm,
+                &references,
+                &ApTracking::default(),
+                &HashMap::new(),
+                Rc::new(struct_types),
+            );
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("ids", PyCell::new(py, ids).unwrap())
+                .unwrap();
+
+            let code = r#"
+ids.ssp[1] = (7, 8)
+ids.ssp[0].x = 5
+assert ids.ssp[1].ptr == 8
+assert ids.ssp[1].address_ == ids.ssp.address_ + 2
+assert ids.sspp.deref().x == 5
+assert ids.sspp[0].deref()[1].x == 7
+"#;
+
+            let py_result = py.run(code, Some(globals), None);
+
+            assert!(py_result.is_ok());
+            //Check ids.ssp[1].x is at the third cell of the array
+            assert_eq!(
+                vm.vm.borrow().get_maybe(&Relocatable::from((2, 2))),
+                Ok(Some(MaybeRelocatable::from(bigint!(7))))
+            );
+
+            //Members of a pointer can't be accessed without dereferencing it
+            let py_result = py.run("ids.sspp.x", Some(globals), None);
+            assert!(py_result
+                .unwrap_err()
+                .is_instance_of::<PyAttributeError>(py));
+
+            //A struct can't be dereferenced
+            let py_result = py.run("ids.ssp.deref()", Some(globals), None);
+            assert!(py_result.unwrap_err().is_instance_of::<PyTypeError>(py));
+        });
+    }
+
+    #[test]
+    fn ids_tuple_references() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            for _ in 0..2 {
+                vm.vm.borrow_mut().add_memory_segment();
+            }
+            //Create references to a tuple at fp and a named tuple at fp + 2
+            let mut references = HashMap::new();
+            for (name, offset, cairo_type) in [
+                ("t", 0, "(felt, felt)"),
+                ("nt", 2, "(x: felt, s: SimpleStruct)"),
+            ] {
+                references.insert(
+                    String::from(name),
+                    HintReference {
+                        offset1: OffsetValue::Reference(Register::FP, offset, false),
+                        offset2: OffsetValue::Value(0),
+                        dereference: true,
+                        ap_tracking_data: None,
+                        cairo_type: Some(String::from(cairo_type)),
+                    },
+                );
+            }
+
+            let struct_types = HashMap::from([create_simple_struct_type()]);
+
+            let ids = PyIds::new(
+                &vm,
+                &references,
+                &ApTracking::default(),
+                &HashMap::new(),
+                Rc::new(struct_types),
+            );
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("ids", PyCell::new(py, ids).unwrap())
+                .unwrap();
+
+            let code = r#"
+ids.t[0] = 1
+ids.t[1] = 2
+assert ids.t[1] == 2
+ids.nt = (3, (4, 5))
+assert ids.nt.x == 3
+assert ids.nt[1].ptr == 5
+assert ids.nt.s.x == 4
+"#;
+
+            let py_result = py.run(code, Some(globals), None);
+
+            assert!(py_result.is_ok());
+            for offset in 0..5 {
+                assert_eq!(
+                    vm.vm.borrow().get_maybe(&Relocatable::from((1, offset))),
+                    Ok(Some(MaybeRelocatable::from(bigint!(offset + 1))))
+                );
+            }
+
+            //ids.t has two members
+            let py_result = py.run("ids.t[2]", Some(globals), None);
+            assert!(py_result.unwrap_err().is_instance_of::<PyIndexError>(py));
+        });
+    }
+
+    #[test]
+    fn parse_tuple_types() {
+        let struct_types = HashMap::from([create_simple_struct_type()]);
+        let members = type_members(
+            &struct_types,
+            "(a: felt, b: (felt, SimpleStruct*), SimpleStruct)",
+        )
+        .unwrap()
+        .into_iter()
+        .map(|(name, member)| (name, member.cairo_type, member.offset))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            members,
+            vec![
+                (Some(String::from("a")), String::from("felt"), 0),
+                (
+                    Some(String::from("b")),
+                    String::from("(felt, SimpleStruct*)"),
+                    1
+                ),
+                (None, String::from("SimpleStruct"), 3),
+            ]
+        );
+        assert_eq!(type_size(&struct_types, "(felt,)"), 1);
+        assert!(type_members(&struct_types, "(felt, felt)*").is_none());
+    }
+
     #[test]
     fn ids_ap_tracked_ref() {
         Python::with_gil(|py| {
@@ -1,29 +1,63 @@
+mod breakpoints;
 pub mod cairo_run;
 pub mod cairo_runner;
+mod cancellation;
+mod dict_manager;
 mod ecdsa;
+mod exec_scopes;
+mod identifier_manager;
 pub mod ids;
 mod instruction_location;
 mod memory;
 mod memory_segments;
 mod range_check;
 mod relocatable;
+mod relocation;
 mod run_context;
+mod run_resources;
+mod sandbox;
 mod scope_manager;
+mod snapshot;
+mod strict_mode;
 mod to_felt_or_relocatable;
 mod utils;
 mod vm_core;
+mod watchpoints;
 
 #[cfg(all(feature = "extension-module", feature = "embedded-python"))]
 compile_error!("\"extension-module\" is incompatible with \"embedded-python\" as it inhibits linking with cpython");
 
+use cairo_run::run_many;
 use cairo_runner::PyCairoRunner;
+use cancellation::{PyCancelToken, RunCancelledError};
+use memory::{FrozenMemoryError, MemoryValidationError};
 use pyo3::prelude::*;
 use relocatable::PyRelocatable;
+use run_resources::{
+    HintsLimitError, MemoryLimitError, PyRunResources, StepsLimitError, TimeLimitError,
+};
+use sandbox::HintSandboxError;
+use strict_mode::UnauthorizedHint;
 
 #[pymodule]
-fn cairo_rs_py(_py: Python, m: &PyModule) -> PyResult<()> {
+fn cairo_rs_py(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyCairoRunner>()?;
     m.add_class::<PyRelocatable>()?;
+    m.add_class::<PyRunResources>()?;
+    m.add_class::<PyCancelToken>()?;
+    m.add_function(wrap_pyfunction!(run_many, m)?)?;
+    m.add("RunCancelledError", py.get_type::<RunCancelledError>())?;
+    m.add("StepsLimitError", py.get_type::<StepsLimitError>())?;
+    m.add("MemoryLimitError", py.get_type::<MemoryLimitError>())?;
+    m.add("TimeLimitError", py.get_type::<TimeLimitError>())?;
+    m.add("HintsLimitError", py.get_type::<HintsLimitError>())?;
+    m.add("UnauthorizedHint", py.get_type::<UnauthorizedHint>())?;
+    m.add("HintSandboxError", py.get_type::<HintSandboxError>())?;
+    m.add(
+        "MemoryValidationError",
+        py.get_type::<MemoryValidationError>(),
+    )?;
+    m.add("FrozenMemoryError", py.get_type::<FrozenMemoryError>())?;
     Ok(())
 }
 
@@ -2,21 +2,49 @@ use crate::{
     relocatable::{PyMaybeRelocatable, PyRelocatable},
     utils::to_py_error,
     vm_core::PyVM,
+    watchpoints::{AccessLog, PyMemoryAccess},
 };
 use cairo_rs::{
     types::relocatable::{MaybeRelocatable, Relocatable},
-    vm::vm_core::VirtualMachine,
+    vm::{
+        errors::memory_errors::MemoryError,
+        vm_core::VirtualMachine,
+        vm_memory::memory::{Memory, ValidationRule},
+    },
 };
 use num_bigint::BigInt;
 use pyo3::{
-    exceptions::{PyTypeError, PyValueError},
+    create_exception,
+    exceptions::{PyException, PyIndexError, PyKeyError, PyTypeError, PyValueError},
     prelude::*,
+    types::{PyBool, PyList},
 };
-use std::{borrow::Cow, cell::RefCell, rc::Rc};
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    fmt::Display,
+    rc::Rc,
+};
+
+create_exception!(cairo_rs_py, MemoryValidationError, PyException);
+create_exception!(cairo_rs_py, FrozenMemoryError, PyException);
+
+thread_local! {
+    /// Why a validation rule added from Python rejected the last write, until the error of the
+    /// write is converted by `memory_write_error` or `convert_rejected_writes`. Each write path
+    /// clears it before writing, so that a rejection can't be taken for the cause of a later
+    /// error.
+    static REJECTED_WRITE: RefCell<Option<PyErr>> = RefCell::new(None);
+}
 
 const MEMORY_GET_ERROR_MSG: &str = "Failed to get value from Cairo memory";
 const MEMORY_SET_ERROR_MSG: &str = "Failed to set value to Cairo memory";
 const MEMORY_GET_RANGE_ERROR_MSG: &str = "Failed to call get_range method from Cairo memory";
+const ACCESS_LOG_DISABLED_MSG: &str =
+    "The access log is disabled, enable it with CairoRunner.enable_access_log()";
+const FROZEN_MEMORY_MSG: &str = "Memory is frozen and can't be written";
+const MEMORY_VIEW_EXPIRED_MSG: &str =
+    "The memory given to a validation rule can't be used once the rule returns";
 const MEMORY_ADD_RELOCATION_RULE_ERROR_MSG: &str =
     "Failed to call add_relocation_rule method from Cairo memory";
 
@@ -24,13 +52,21 @@ const MEMORY_ADD_RELOCATION_RULE_ERROR_MSG: &str =
 #[derive(Clone)]
 pub struct PyMemory {
     vm: Rc<RefCell<VirtualMachine>>,
+    access_log: AccessLog,
+    pub(crate) frozen: Rc<Cell<bool>>,
+    has_py_validation_rules: Rc<Cell<bool>>,
 }
 
 #[pymethods]
 impl PyMemory {
     #[new]
     pub fn new(vm: &PyVM) -> PyMemory {
-        PyMemory { vm: vm.get_vm() }
+        PyMemory {
+            vm: vm.get_vm(),
+            access_log: Rc::clone(&vm.access_log),
+            frozen: Rc::clone(&vm.memory_frozen),
+            has_py_validation_rules: Rc::clone(&vm.has_py_validation_rules),
+        }
     }
 
     #[getter]
@@ -50,11 +86,12 @@ impl PyMemory {
     pub fn __setitem__(&self, key: &PyRelocatable, value: PyMaybeRelocatable) -> PyResult<()> {
         let key: Relocatable = key.into();
         let value: MaybeRelocatable = value.into();
+        check_not_frozen(&self.frozen)?;
 
-        self.vm
-            .borrow_mut()
-            .insert_value(&key, value)
-            .map_err(|_| PyValueError::new_err(MEMORY_SET_ERROR_MSG))
+        clear_rejected_write();
+        self.vm.borrow_mut().insert_value(&key, value).map_err(|_| {
+            take_rejected_write().unwrap_or_else(|| PyValueError::new_err(MEMORY_SET_ERROR_MSG))
+        })
     }
 
     pub fn get_range(
@@ -79,6 +116,7 @@ impl PyMemory {
         src_ptr: PyRelocatable,
         dest_ptr: PyRelocatable,
     ) -> Result<(), PyErr> {
+        check_not_frozen(&self.frozen)?;
         self.vm
             .borrow_mut()
             .add_relocation_rule(Relocatable::from(&src_ptr), Relocatable::from(&dest_ptr))
@@ -96,6 +134,311 @@ impl PyMemory {
             .map(Cow::into_owned)
             .collect())
     }
+
+    /// Calls `rule(memory, address)` each time a cell of the segment is written, as cairo-lang
+    /// does, failing the write with `MemoryValidationError` if it raises or returns `False`.
+    /// `memory` is a read-only `MemoryView`, through which the rule can read the other cells, and
+    /// which can't be used once the rule returns. The rule the segment already has, such as the
+    /// range check of a builtin, still runs first.
+    pub fn add_validation_rule(&self, segment_index: usize, rule: &PyAny) -> PyResult<()> {
+        if !rule.is_callable() {
+            return Err(PyTypeError::new_err(format!(
+                "Validation rule must be callable, got {}",
+                rule.get_type().name()?
+            )));
+        }
+        let rule: PyObject = rule.into();
+        self.has_py_validation_rules.set(true);
+        let mut vm = self.vm.borrow_mut();
+        // A segment has a single rule in the Rust VM, so the new one wraps the existing one
+        let previous = vm.memory.validation_rules.remove(&segment_index);
+        vm.memory.add_validation_rule(
+            segment_index,
+            ValidationRule(Box::new(
+                move |memory: &Memory, address: &MaybeRelocatable| {
+                    if let Some(previous) = &previous {
+                        (previous.0)(memory, address)?;
+                    }
+                    validate_with_py_rule(&rule, memory, address)
+                },
+            )),
+        );
+        Ok(())
+    }
+
+    /// Makes any further write to the memory, including by running more steps, fail with
+    /// `FrozenMemoryError`, as cairo-lang does after `end_run`. It can't be undone.
+    pub fn freeze(&self) {
+        self.frozen.set(true);
+    }
+
+    #[getter]
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.get()
+    }
+
+    /// Returns the accesses to `address`, or to all cells, logged since
+    /// `CairoRunner.enable_access_log` was called.
+    #[args(address = "None")]
+    pub fn access_log(&self, address: Option<PyRelocatable>) -> PyResult<Vec<PyMemoryAccess>> {
+        let access_log = self.access_log.borrow();
+        let access_log = access_log
+            .as_ref()
+            .ok_or_else(|| PyValueError::new_err(ACCESS_LOG_DISABLED_MSG))?;
+        let address = address.map(|address| Relocatable::from(&address));
+        Ok(access_log
+            .iter()
+            .filter(|access| address.map_or(true, |address| access.address == address))
+            .cloned()
+            .collect())
+    }
+
+    pub fn __contains__(&self, key: &PyRelocatable) -> bool {
+        matches!(self.vm.borrow().get_maybe(key), Ok(Some(_)))
+    }
+
+    /// Returns the value at `key`, or `default` if the cell is unknown.
+    #[args(default = "None")]
+    pub fn get(&self, key: &PyRelocatable, default: Option<PyObject>, py: Python) -> PyObject {
+        match self.vm.borrow().get_maybe(key) {
+            Ok(Some(value)) => PyMaybeRelocatable::from(value).to_object(py),
+            _ => default.unwrap_or_else(|| py.None()),
+        }
+    }
+
+    /// Number of known cells, in all segments.
+    pub fn __len__(&self) -> usize {
+        self.vm
+            .borrow()
+            .memory
+            .data
+            .iter()
+            .map(|segment| segment.iter().flatten().count())
+            .sum()
+    }
+
+    /// Iterates over the addresses of the known cells, segment by segment.
+    pub fn __iter__(&self, py: Python) -> PyResult<PyObject> {
+        let keys = self.keys(None)?;
+        Ok(PyList::new(py, keys.into_iter().map(|key| key.into_py(py)))
+            .call_method0("__iter__")?
+            .into())
+    }
+
+    /// Returns the addresses of the known cells of `segment_index`, or of all segments.
+    #[args(segment_index = "None")]
+    pub fn keys(&self, segment_index: Option<isize>) -> PyResult<Vec<PyRelocatable>> {
+        Ok(self
+            .cells(segment_index)?
+            .into_iter()
+            .map(|(key, _)| PyRelocatable::from(key))
+            .collect())
+    }
+
+    /// Returns the values of the known cells of `segment_index`, or of all segments.
+    #[args(segment_index = "None")]
+    pub fn values(&self, segment_index: Option<isize>, py: Python) -> PyResult<Vec<PyObject>> {
+        Ok(self
+            .cells(segment_index)?
+            .into_iter()
+            .map(|(_, value)| PyMaybeRelocatable::from(value).to_object(py))
+            .collect())
+    }
+
+    /// Returns the (address, value) pairs of the known cells of `segment_index`, or of all
+    /// segments.
+    #[args(segment_index = "None")]
+    pub fn items(
+        &self,
+        segment_index: Option<isize>,
+        py: Python,
+    ) -> PyResult<Vec<(PyRelocatable, PyObject)>> {
+        Ok(self
+            .cells(segment_index)?
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    PyRelocatable::from(key),
+                    PyMaybeRelocatable::from(value).to_object(py),
+                )
+            })
+            .collect())
+    }
+}
+
+impl PyMemory {
+    /// Returns the known cells of a (non temporary) segment, or of all of them, in order.
+    fn cells(
+        &self,
+        segment_index: Option<isize>,
+    ) -> PyResult<Vec<(Relocatable, MaybeRelocatable)>> {
+        let vm = self.vm.borrow();
+        let data = &vm.memory.data;
+        let segments = match segment_index {
+            Some(segment_index) => {
+                let index = usize::try_from(segment_index)
+                    .ok()
+                    .filter(|index| *index < data.len())
+                    .ok_or_else(|| {
+                        PyIndexError::new_err(format!("Unknown segment {segment_index}"))
+                    })?;
+                index..index + 1
+            }
+            None => 0..data.len(),
+        };
+        Ok(segments
+            .flat_map(|index| {
+                data[index]
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(offset, cell)| {
+                        cell.as_ref().map(|value| {
+                            (Relocatable::from((index as isize, offset)), value.clone())
+                        })
+                    })
+            })
+            .collect())
+    }
+}
+
+/// The memory as validation rules added from Python read it: a read-only view of the memory being
+/// validated, which the rule gets instead of the VM's memory as that one is borrowed by the write.
+#[pyclass(unsendable, name = "MemoryView")]
+pub struct PyMemoryView {
+    /// The memory being validated, until the rule returns.
+    memory: Option<*const Memory>,
+}
+
+#[pymethods]
+impl PyMemoryView {
+    pub fn __getitem__(&self, key: &PyRelocatable, py: Python) -> PyResult<PyObject> {
+        self.get_value(key)?
+            .map(|value| value.to_object(py))
+            .ok_or_else(|| PyKeyError::new_err(key.__repr__()))
+    }
+
+    /// Returns the value at `key`, or `default` if the cell is unknown.
+    #[args(default = "None")]
+    pub fn get(
+        &self,
+        key: &PyRelocatable,
+        default: Option<PyObject>,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        Ok(match self.get_value(key)? {
+            Some(value) => value.to_object(py),
+            None => default.unwrap_or_else(|| py.None()),
+        })
+    }
+
+    pub fn __contains__(&self, key: &PyRelocatable) -> PyResult<bool> {
+        Ok(self.get_value(key)?.is_some())
+    }
+}
+
+impl PyMemoryView {
+    fn get_value(&self, key: &PyRelocatable) -> PyResult<Option<PyMaybeRelocatable>> {
+        let memory = self
+            .memory
+            .ok_or_else(|| PyValueError::new_err(MEMORY_VIEW_EXPIRED_MSG))?;
+        // SAFETY: the pointer is only set while the rule runs, during which the memory is
+        // borrowed by the write being validated, so it can neither move nor change.
+        let memory = unsafe { &*memory };
+        match memory.get(&MaybeRelocatable::from(Relocatable::from(key))) {
+            Ok(Some(value)) => Ok(Some(PyMaybeRelocatable::from(&*value))),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Runs a validation rule added with `PyMemory.add_validation_rule` on the cell at `address`.
+fn validate_with_py_rule(
+    rule: &PyObject,
+    memory: &Memory,
+    address: &MaybeRelocatable,
+) -> Result<MaybeRelocatable, MemoryError> {
+    let value = match memory.get(address) {
+        Ok(Some(value)) => PyMaybeRelocatable::from(&*value),
+        _ => return Ok(address.clone()),
+    };
+    Python::with_gil(|py| {
+        let address_obj = PyMaybeRelocatable::from(address.clone()).to_object(py);
+        let value = value.to_object(py);
+        let view = Py::new(
+            py,
+            PyMemoryView {
+                memory: Some(memory as *const Memory),
+            },
+        )?;
+        let result = rule.call1(py, (view.clone_ref(py), address_obj.clone_ref(py)));
+        view.borrow_mut(py).memory = None;
+        let rejection = match result {
+            Ok(result) if result.as_ref(py).is(PyBool::new(py, false)) => {
+                String::from("returned False")
+            }
+            Ok(_) => return Ok(address.clone()),
+            Err(error) => format!("raised {error}"),
+        };
+        let rule = rule.as_ref(py);
+        let name = rule
+            .getattr("__name__")
+            .map_or_else(|_| rule.to_string(), |name| name.to_string());
+        Err(MemoryValidationError::new_err(format!(
+            "Validation rule '{name}' rejected value {} at address {}: it {rejection}",
+            value.as_ref(py).repr()?,
+            address_obj.as_ref(py).repr()?
+        )))
+    })
+    .map_err(|error| {
+        // The error of the write can't carry it, so it's kept until the write fails
+        REJECTED_WRITE.with(|rejected| *rejected.borrow_mut() = Some(error));
+        MemoryError::NumOutOfBounds
+    })
+}
+
+fn take_rejected_write() -> Option<PyErr> {
+    REJECTED_WRITE.with(|rejected| rejected.borrow_mut().take())
+}
+
+fn clear_rejected_write() {
+    take_rejected_write();
+}
+
+/// Converts the error of a failed memory write, which is the rejection of a validation rule added
+/// from Python if one caused it.
+fn memory_write_error<T: Display>(error: T) -> PyErr {
+    take_rejected_write().unwrap_or_else(|| to_py_error(error))
+}
+
+/// Runs `f`, whose memory writes don't convert their errors, as those of the builtin hints, and
+/// fails with the rejection of a validation rule if it failed because of one. No rejection
+/// outlives it.
+pub(crate) fn convert_rejected_writes<T>(f: impl FnOnce() -> PyResult<T>) -> PyResult<T> {
+    clear_rejected_write();
+    let result = f();
+    match take_rejected_write() {
+        Some(rejection) if result.is_err() => Err(rejection),
+        _ => result,
+    }
+}
+
+/// Runs a memory write, converting its error with `memory_write_error`.
+pub(crate) fn write_memory<T, E: Display>(write: impl FnOnce() -> Result<T, E>) -> PyResult<T> {
+    clear_rejected_write();
+    write().map_err(memory_write_error)
+}
+
+pub(crate) fn check_not_frozen(frozen: &Cell<bool>) -> PyResult<()> {
+    if frozen.get() {
+        return Err(FrozenMemoryError::new_err(FROZEN_MEMORY_MSG));
+    }
+    Ok(())
+}
+
+/// Returns the number of cells allocated in each (non temporary) segment of the VM's memory,
+/// holes included.
+pub(crate) fn segment_sizes(vm: &VirtualMachine) -> Vec<usize> {
+    vm.memory.data.iter().map(Vec::len).collect()
 }
 
 #[cfg(test)]
@@ -103,12 +446,15 @@ mod test {
     use crate::relocatable::PyMaybeRelocatable;
     use crate::relocatable::PyMaybeRelocatable::RelocatableValue;
     use crate::vm_core::PyVM;
-    use crate::{memory::PyMemory, relocatable::PyRelocatable};
+    use crate::{
+        memory::{FrozenMemoryError, MemoryValidationError, PyMemory},
+        relocatable::PyRelocatable,
+    };
     use cairo_rs::bigint;
     use cairo_rs::types::relocatable::{MaybeRelocatable, Relocatable};
     use num_bigint::{BigInt, Sign};
     use pyo3::PyCell;
-    use pyo3::{types::PyDict, Python};
+    use pyo3::{exceptions::PyValueError, types::PyDict, Python};
 
     #[test]
     fn memory_insert_test() {
@@ -413,4 +759,204 @@ assert memory[ap] == fp
             .get_range_as_ints(addr.into(), 8)
             .expect_err("get_range_as_ints() succeeded (should have failed)");
     }
+
+    #[test]
+    fn memory_dict_protocol() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            {
+                let mut vm = vm.vm.borrow_mut();
+                for _ in 0..2 {
+                    vm.add_memory_segment();
+                }
+                vm.insert_value(
+                    &Relocatable::from((0, 0)),
+                    &MaybeRelocatable::from(bigint!(1)),
+                )
+                .unwrap();
+                vm.insert_value(&Relocatable::from((1, 2)), &MaybeRelocatable::from((0, 0)))
+                    .unwrap();
+            }
+            let memory = PyMemory::new(&vm);
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("memory", PyCell::new(py, memory).unwrap())
+                .unwrap();
+            globals
+                .set_item(
+                    "addr",
+                    PyCell::new(py, PyRelocatable::from((1, 2))).unwrap(),
+                )
+                .unwrap();
+
+            let code = r#"
+base = addr - 2
+assert addr in memory
+assert base not in memory
+assert memory.get(base) is None
+assert memory.get(base, 7) == 7
+assert memory.get(addr) == memory.keys(0)[0]
+assert len(memory) == 2
+assert list(memory) == memory.keys()
+assert memory.items(1) == [(addr, memory[addr])]
+assert memory.values(0) == [1]
+"#;
+
+            let py_result = py.run(code, Some(globals), None);
+
+            assert!(py_result.is_ok());
+            assert!(py.run("memory.items(2)", Some(globals), None).is_err());
+        });
+    }
+
+    #[test]
+    fn memory_validation_rule() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            vm.vm.borrow_mut().add_memory_segment();
+            let memory = PyMemory::new(&vm);
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("memory", PyCell::new(py, memory).unwrap())
+                .unwrap();
+            globals
+                .set_item(
+                    "addr",
+                    PyCell::new(py, PyRelocatable::from((0, 0))).unwrap(),
+                )
+                .unwrap();
+
+            let code = r#"
+def is_even(memory, addr):
+    return memory[addr] % 2 == 0
+
+memory.add_validation_rule(0, is_even)
+memory[addr] = 2
+"#;
+
+            let py_result = py.run(code, Some(globals), None);
+
+            assert!(py_result.is_ok());
+            let error = py
+                .run("memory[addr + 1] = 3", Some(globals), None)
+                .unwrap_err();
+            assert!(error.is_instance_of::<MemoryValidationError>(py));
+            assert!(error.to_string().contains("is_even"));
+            assert!(py
+                .run("memory.add_validation_rule(0, 1)", Some(globals), None)
+                .is_err());
+
+            // A second rule doesn't replace the first one
+            let code = r#"
+def is_small(memory, addr):
+    return memory[addr] < 10
+
+memory.add_validation_rule(0, is_small)
+memory[addr + 2] = 4
+"#;
+            py.run(code, Some(globals), None).unwrap();
+            let error = py
+                .run("memory[addr + 3] = 5", Some(globals), None)
+                .unwrap_err();
+            assert!(error.to_string().contains("is_even"));
+            let error = py
+                .run("memory[addr + 4] = 12", Some(globals), None)
+                .unwrap_err();
+            assert!(error.to_string().contains("is_small"));
+        });
+    }
+
+    #[test]
+    fn memory_validation_rule_reads_memory() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            vm.vm.borrow_mut().add_memory_segment();
+            let memory = PyMemory::new(&vm);
+
+            let globals = PyDict::new(py);
+            globals
+                .set_item("memory", PyCell::new(py, memory).unwrap())
+                .unwrap();
+            globals
+                .set_item(
+                    "addr",
+                    PyCell::new(py, PyRelocatable::from((0, 0))).unwrap(),
+                )
+                .unwrap();
+
+            // Each value at an odd offset must be the double of the one before it, as the pairs
+            // of the signature builtin are checked together
+            let code = r#"
+views = []
+def is_double(memory, addr):
+    views.append(memory)
+    return addr.offset % 2 == 0 or memory[addr] == 2 * memory.get(addr - 1, 0)
+
+memory.add_validation_rule(0, is_double)
+memory[addr] = 3
+memory[addr + 1] = 6
+"#;
+            py.run(code, Some(globals), None).unwrap();
+            let error = py
+                .run("memory[addr + 3] = 6", Some(globals), None)
+                .unwrap_err();
+            assert!(error.is_instance_of::<MemoryValidationError>(py));
+            assert!(py
+                .run("views[0][addr]", Some(globals), None)
+                .unwrap_err()
+                .is_instance_of::<PyValueError>(py));
+        });
+    }
+
+    #[test]
+    fn memory_freeze() {
+        Python::with_gil(|py| {
+            let vm = PyVM::new(
+                BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+                false,
+                Vec::new(),
+            );
+            vm.vm.borrow_mut().add_memory_segment();
+            let memory = PyMemory::new(&vm);
+            let addr = PyRelocatable::from((0, 0));
+
+            memory
+                .__setitem__(&addr, PyMaybeRelocatable::Int(bigint!(1)))
+                .unwrap();
+            assert!(!memory.is_frozen());
+            memory.freeze();
+            assert!(PyMemory::new(&vm).is_frozen());
+
+            let error = memory
+                .__setitem__(&(0, 1).into(), PyMaybeRelocatable::Int(bigint!(2)))
+                .unwrap_err();
+            assert!(error.is_instance_of::<FrozenMemoryError>(py));
+            assert!(memory
+                .add_relocation_rule((0, 1).into(), (0, 0).into())
+                .is_err());
+            assert_eq!(
+                memory
+                    .__getitem__(&addr, py)
+                    .unwrap()
+                    .unwrap()
+                    .extract::<BigInt>(py)
+                    .unwrap(),
+                bigint!(1)
+            );
+        });
+    }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    memory::PyMemory,
+    memory::{check_not_frozen, write_memory, PyMemory},
     relocatable::{PyMaybeRelocatable, PyRelocatable},
     utils::to_py_error,
     vm_core::PyVM,
@@ -26,6 +26,7 @@ impl PySegmentManager {
     }
 
     pub fn add(&self) -> PyResult<PyRelocatable> {
+        check_not_frozen(&self.memory.frozen)?;
         Ok(self.vm.borrow_mut().add_memory_segment().into())
     }
 
@@ -36,6 +37,7 @@ impl PySegmentManager {
         arg: Py<PyAny>,
         apply_modulo_to_args: bool,
     ) -> PyResult<PyObject> {
+        check_not_frozen(&self.memory.frozen)?;
         Ok(
             PyMaybeRelocatable::from(match PyIterator::from_object(py, &arg) {
                 Ok(iterator) => {
@@ -73,6 +75,7 @@ impl PySegmentManager {
         apply_modulo_to_args: bool,
     ) -> PyResult<PyObject> {
         let ptr: MaybeRelocatable = ptr.into();
+        check_not_frozen(&self.memory.frozen)?;
 
         let arg_iter = PyIterator::from_object(py, &arg)?;
         let mut data = Vec::new();
@@ -84,14 +87,12 @@ impl PySegmentManager {
             );
         }
 
-        self.vm
-            .borrow_mut()
-            .load_data(&ptr, data)
+        write_memory(|| self.vm.borrow_mut().load_data(&ptr, data))
             .map(|x| PyMaybeRelocatable::from(x).to_object(py))
-            .map_err(to_py_error)
     }
 
     pub fn add_temp_segment(&mut self) -> PyResult<PyRelocatable> {
+        check_not_frozen(&self.memory.frozen)?;
         Ok(PyRelocatable::from(
             self.vm.borrow_mut().add_temporary_segment(),
         ))
@@ -105,7 +106,11 @@ impl PySegmentManager {
 #[cfg(test)]
 mod test {
     use super::PySegmentManager;
-    use crate::{memory::PyMemory, relocatable::PyMaybeRelocatable, vm_core::PyVM};
+    use crate::{
+        memory::{FrozenMemoryError, PyMemory},
+        relocatable::PyMaybeRelocatable,
+        vm_core::PyVM,
+    };
     use cairo_rs::{bigint, types::relocatable::Relocatable};
     use num_bigint::{BigInt, Sign};
     use pyo3::{Python, ToPyObject};
@@ -121,6 +126,33 @@ mod test {
         assert!(segments.add().is_ok());
     }
 
+    #[test]
+    fn add_segment_to_frozen_memory() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let memory = PyMemory::new(&vm);
+        memory.freeze();
+        let mut segments = PySegmentManager::new(&mut vm, memory);
+        Python::with_gil(|py| {
+            assert!(segments
+                .add()
+                .unwrap_err()
+                .is_instance_of::<FrozenMemoryError>(py));
+            assert!(segments
+                .add_temp_segment()
+                .unwrap_err()
+                .is_instance_of::<FrozenMemoryError>(py));
+            assert!(segments
+                .gen_arg(py, vec![1].to_object(py), true)
+                .unwrap_err()
+                .is_instance_of::<FrozenMemoryError>(py));
+        });
+        assert!(vm.vm.borrow().memory.data.is_empty());
+    }
+
     #[test]
     fn write_arg_test() {
         Python::with_gil(|py| {
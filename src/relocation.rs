@@ -0,0 +1,129 @@
+use cairo_rs::vm::trace::trace_entry::RelocatedTraceEntry;
+use num_bigint::BigInt;
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
+
+/// Size of an address or register in the binary memory and trace files.
+const WORD_SIZE: usize = 8;
+/// Size of a value in the binary memory file.
+const FELT_SIZE: usize = 32;
+
+#[pyclass(name = "RelocatedTraceEntry")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyRelocatedTraceEntry {
+    #[pyo3(get)]
+    pc: usize,
+    #[pyo3(get)]
+    ap: usize,
+    #[pyo3(get)]
+    fp: usize,
+}
+
+#[pymethods]
+impl PyRelocatedTraceEntry {
+    fn __repr__(&self) -> String {
+        format!(
+            "RelocatedTraceEntry(pc={}, ap={}, fp={})",
+            self.pc, self.ap, self.fp
+        )
+    }
+}
+
+impl From<&RelocatedTraceEntry> for PyRelocatedTraceEntry {
+    fn from(entry: &RelocatedTraceEntry) -> Self {
+        PyRelocatedTraceEntry {
+            pc: entry.pc,
+            ap: entry.ap,
+            fp: entry.fp,
+        }
+    }
+}
+
+/// Encodes the relocated memory as `write_binary_memory` does: an 8-byte address followed by a
+/// 32-byte value for each known cell, both little-endian. The bytes are written straight into
+/// the returned object.
+pub(crate) fn memory_to_bytes<'py>(
+    py: Python<'py>,
+    relocated_memory: &[Option<BigInt>],
+) -> PyResult<&'py PyBytes> {
+    let cells = relocated_memory
+        .iter()
+        .filter(|value| value.is_some())
+        .count();
+    PyBytes::new_with(py, cells * (WORD_SIZE + FELT_SIZE), |buffer| {
+        let cells = relocated_memory
+            .iter()
+            .enumerate()
+            .filter_map(|(address, value)| Some((address, value.as_ref()?)));
+        for ((address, value), chunk) in cells.zip(buffer.chunks_exact_mut(WORD_SIZE + FELT_SIZE)) {
+            let (address_bytes, value_bytes) = chunk.split_at_mut(WORD_SIZE);
+            address_bytes.copy_from_slice(&(address as u64).to_le_bytes());
+            let value = value.to_bytes_le().1;
+            if value.len() > FELT_SIZE {
+                return Err(PyValueError::new_err(format!(
+                    "The value at address {address} doesn't fit in {FELT_SIZE} bytes"
+                )));
+            }
+            value_bytes[..value.len()].copy_from_slice(&value);
+        }
+        Ok(())
+    })
+}
+
+/// Encodes the relocated trace as `write_binary_trace` does: the ap, fp and pc of each entry, as
+/// 8-byte little-endian integers. The bytes are written straight into the returned object.
+pub(crate) fn trace_to_bytes<'py>(
+    py: Python<'py>,
+    relocated_trace: &[RelocatedTraceEntry],
+) -> PyResult<&'py PyBytes> {
+    PyBytes::new_with(py, relocated_trace.len() * 3 * WORD_SIZE, |buffer| {
+        for (entry, chunk) in relocated_trace
+            .iter()
+            .zip(buffer.chunks_exact_mut(3 * WORD_SIZE))
+        {
+            for (register, bytes) in [entry.ap, entry.fp, entry.pc]
+                .into_iter()
+                .zip(chunk.chunks_exact_mut(WORD_SIZE))
+            {
+                bytes.copy_from_slice(&(register as u64).to_le_bytes());
+            }
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn binary_encodings() {
+        Python::with_gil(|py| {
+            let memory = vec![
+                None,
+                Some(BigInt::from(0x0102)),
+                None,
+                Some(BigInt::from(0)),
+            ];
+            let bytes = memory_to_bytes(py, &memory).unwrap().as_bytes();
+            assert_eq!(bytes.len(), 80);
+            assert_eq!(&bytes[..10], &[1, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x01]);
+            assert!(bytes[10..40].iter().all(|byte| *byte == 0));
+            assert_eq!(&bytes[40..48], &3u64.to_le_bytes());
+            assert!(bytes[48..].iter().all(|byte| *byte == 0));
+
+            let trace = vec![RelocatedTraceEntry {
+                pc: 1,
+                ap: 2,
+                fp: 3,
+            }];
+            let bytes = trace_to_bytes(py, &trace).unwrap().as_bytes();
+            assert_eq!(
+                bytes,
+                [2u64, 3, 1]
+                    .iter()
+                    .flat_map(|register| register.to_le_bytes())
+                    .collect::<Vec<_>>()
+            );
+        });
+    }
+}
@@ -0,0 +1,253 @@
+use pyo3::{create_exception, exceptions::PyValueError, prelude::*};
+
+pyo3::import_exception!(starkware.cairo.lang.vm.utils, ResourcesError);
+create_exception!(cairo_rs_py, StepsLimitError, ResourcesError);
+create_exception!(cairo_rs_py, MemoryLimitError, ResourcesError);
+create_exception!(cairo_rs_py, TimeLimitError, ResourcesError);
+create_exception!(cairo_rs_py, HintsLimitError, ResourcesError);
+
+const STEPS_LIMIT_MSG: &str = "Error: Execution reached the end of the program.";
+
+/// Limits for a run, along with the resources it has consumed so far.
+/// Consumption accumulates when the same object is used for several runs.
+/// Limits are checked between steps: a hint running past `time_limit` isn't interrupted, the run
+/// stops with `TimeLimitError` before the next step.
+#[pyclass(name = "RunResources")]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PyRunResources {
+    #[pyo3(get, set)]
+    pub n_steps: Option<usize>,
+    #[pyo3(get, set)]
+    pub n_memory_cells: Option<usize>,
+    /// Wall-clock limit, in seconds, checked between steps.
+    #[pyo3(get, set)]
+    pub time_limit: Option<f64>,
+    /// Limit on the number of hints executed as Python code.
+    #[pyo3(get, set)]
+    pub n_hints: Option<usize>,
+    #[pyo3(get)]
+    pub consumed_steps: usize,
+    /// Memory cells allocated by the VM at the end of the last run, or of the last step when
+    /// `n_memory_cells` is set, holes included.
+    #[pyo3(get)]
+    pub consumed_memory_cells: usize,
+    #[pyo3(get)]
+    pub consumed_time: f64,
+    #[pyo3(get)]
+    pub consumed_hints: usize,
+}
+
+#[pymethods]
+impl PyRunResources {
+    #[new]
+    #[args(
+        n_steps = "None",
+        n_memory_cells = "None",
+        time_limit = "None",
+        n_hints = "None"
+    )]
+    pub fn new(
+        n_steps: Option<usize>,
+        n_memory_cells: Option<usize>,
+        time_limit: Option<f64>,
+        n_hints: Option<usize>,
+    ) -> PyRunResources {
+        PyRunResources {
+            n_steps,
+            n_memory_cells,
+            time_limit,
+            n_hints,
+            ..Default::default()
+        }
+    }
+}
+
+impl PyRunResources {
+    /// Reads the limits of a run from `run_resources`, which can be a `RunResources` of this
+    /// module, a cairo-lang `RunResources` (only `n_steps` is read) or a number of steps.
+    pub(crate) fn from_py(run_resources: Option<&PyAny>) -> PyResult<PyRunResources> {
+        let run_resources = match run_resources {
+            Some(run_resources) if !run_resources.is_none() => run_resources,
+            _ => return Ok(PyRunResources::default()),
+        };
+        if let Ok(run_resources) = run_resources.downcast::<PyCell<PyRunResources>>() {
+            return Ok(run_resources.borrow().clone());
+        }
+        let n_steps = match run_resources.extract::<usize>() {
+            Ok(n_steps) => Some(n_steps),
+            Err(_) => run_resources.getattr("n_steps")?.extract()?,
+        };
+        Ok(PyRunResources {
+            n_steps,
+            ..Default::default()
+        })
+    }
+
+    /// Reports the consumed resources back to the object the limits were read from.
+    /// A cairo-lang `RunResources` has its `n_steps` decreased, as its `consume_step` would.
+    pub(crate) fn update_py(&self, run_resources: Option<&PyAny>) -> PyResult<()> {
+        let run_resources = match run_resources {
+            Some(run_resources) if !run_resources.is_none() => run_resources,
+            _ => return Ok(()),
+        };
+        if let Ok(run_resources) = run_resources.downcast::<PyCell<PyRunResources>>() {
+            *run_resources.borrow_mut() = self.clone();
+        } else if run_resources.extract::<usize>().is_err() {
+            if let Some(n_steps) = self.n_steps {
+                run_resources.setattr("n_steps", n_steps.saturating_sub(self.consumed_steps))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the limits that must hold before executing another step.
+    pub(crate) fn check_before_step(&self) -> PyResult<()> {
+        if matches!(self.n_steps, Some(n_steps) if self.consumed_steps >= n_steps) {
+            return Err(StepsLimitError::new_err(PyValueError::new_err(
+                STEPS_LIMIT_MSG,
+            )));
+        }
+        if matches!(self.time_limit, Some(time_limit) if self.consumed_time >= time_limit) {
+            return Err(TimeLimitError::new_err(format!(
+                "Error: Execution exceeded the time limit of {} seconds.",
+                self.time_limit.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether the memory must be measured after each step, as there's a limit on it.
+    pub(crate) fn limits_memory(&self) -> bool {
+        self.n_memory_cells.is_some()
+    }
+
+    /// Records the resources consumed by a step and checks the limits that it may have exceeded.
+    /// `memory_cells` is only needed when the memory is limited.
+    pub(crate) fn consume_step(
+        &mut self,
+        py_hints_run: usize,
+        memory_cells: Option<usize>,
+        consumed_time: f64,
+    ) -> PyResult<()> {
+        self.consumed_steps += 1;
+        self.consumed_hints += py_hints_run;
+        if let Some(memory_cells) = memory_cells {
+            self.consumed_memory_cells = memory_cells;
+        }
+        self.consumed_time = consumed_time;
+
+        if let Some(n_memory_cells) = self.n_memory_cells {
+            if self.consumed_memory_cells > n_memory_cells {
+                return Err(MemoryLimitError::new_err(format!(
+                    "Error: Execution allocated {} memory cells, exceeding the limit of {n_memory_cells}.",
+                    self.consumed_memory_cells
+                )));
+            }
+        }
+        if let Some(n_hints) = self.n_hints {
+            if self.consumed_hints > n_hints {
+                return Err(HintsLimitError::new_err(format!(
+                    "Error: Execution ran {} Python hints, exceeding the limit of {n_hints}.",
+                    self.consumed_hints
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_py_number_of_steps() {
+        Python::with_gil(|py| {
+            assert_eq!(
+                PyRunResources::from_py(Some(py.eval("10", None, None).unwrap())).unwrap(),
+                PyRunResources::new(Some(10), None, None, None)
+            );
+            assert_eq!(
+                PyRunResources::from_py(Some(py.None().into_ref(py))).unwrap(),
+                PyRunResources::default()
+            );
+            assert_eq!(
+                PyRunResources::from_py(None).unwrap(),
+                PyRunResources::default()
+            );
+        });
+    }
+
+    #[test]
+    fn from_py_object_with_n_steps() {
+        Python::with_gil(|py| {
+            let run_resources = py
+                .eval("type('RunResources', (), {'n_steps': 5})()", None, None)
+                .unwrap();
+            let resources = PyRunResources::from_py(Some(run_resources)).unwrap();
+            assert_eq!(resources.n_steps, Some(5));
+
+            let resources = PyRunResources {
+                consumed_steps: 3,
+                ..resources
+            };
+            resources.update_py(Some(run_resources)).unwrap();
+            assert_eq!(
+                run_resources
+                    .getattr("n_steps")
+                    .unwrap()
+                    .extract::<usize>()
+                    .unwrap(),
+                2
+            );
+        });
+    }
+
+    #[test]
+    fn update_py_run_resources() {
+        Python::with_gil(|py| {
+            let run_resources =
+                PyCell::new(py, PyRunResources::new(Some(5), None, None, None)).unwrap();
+            let mut resources = PyRunResources::from_py(Some(run_resources.as_ref())).unwrap();
+            resources.consume_step(1, Some(10), 1.0).unwrap();
+            resources.update_py(Some(run_resources.as_ref())).unwrap();
+
+            assert_eq!(run_resources.borrow().consumed_steps, 1);
+            assert_eq!(run_resources.borrow().consumed_hints, 1);
+            assert_eq!(run_resources.borrow().consumed_memory_cells, 10);
+            assert_eq!(run_resources.borrow().consumed_time, 1.0);
+        });
+    }
+
+    #[test]
+    fn exceeding_limits() {
+        Python::with_gil(|py| {
+            let mut resources = PyRunResources::new(Some(1), Some(10), Some(5.0), Some(1));
+            assert!(resources.check_before_step().is_ok());
+            assert!(resources.consume_step(1, Some(10), 1.0).is_ok());
+            assert!(resources
+                .check_before_step()
+                .unwrap_err()
+                .is_instance_of::<StepsLimitError>(py));
+
+            let mut resources = PyRunResources::new(None, Some(10), None, None);
+            assert!(resources
+                .consume_step(0, Some(11), 0.0)
+                .unwrap_err()
+                .is_instance_of::<MemoryLimitError>(py));
+
+            let mut resources = PyRunResources::new(None, None, None, Some(1));
+            assert!(resources
+                .consume_step(2, Some(0), 0.0)
+                .unwrap_err()
+                .is_instance_of::<HintsLimitError>(py));
+
+            let mut resources = PyRunResources::new(None, None, Some(5.0), None);
+            resources.consume_step(0, Some(0), 6.0).unwrap();
+            assert!(resources
+                .check_before_step()
+                .unwrap_err()
+                .is_instance_of::<TimeLimitError>(py));
+        });
+    }
+}
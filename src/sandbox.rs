@@ -0,0 +1,525 @@
+use pyo3::{
+    create_exception,
+    exceptions::PyException,
+    prelude::*,
+    types::{PyDict, PyTuple, PyType},
+};
+use std::collections::HashSet;
+
+create_exception!(cairo_rs_py, HintSandboxError, PyException);
+
+/// Modules that hints can import in sandbox mode unless others are given: the leaf modules that
+/// the hints of the Cairo common library use, rather than whole packages.
+const DEFAULT_ALLOWED_MODULES: [&str; 14] = [
+    "math",
+    "starkware.cairo.common.cairo_blake2s.blake2s_utils",
+    "starkware.cairo.common.cairo_keccak.keccak_utils",
+    "starkware.cairo.common.cairo_secp.secp_utils",
+    "starkware.cairo.common.cairo_sha256.sha256_utils",
+    "starkware.cairo.common.dict",
+    "starkware.cairo.common.keccak_utils.keccak_utils",
+    "starkware.cairo.common.math_utils",
+    "starkware.cairo.common.structs",
+    "starkware.cairo.lang.vm.crypto",
+    "starkware.cairo.lang.vm.relocatable",
+    "starkware.crypto.signature.signature",
+    "starkware.python.math_utils",
+    "starkware.python.utils",
+];
+
+/// Builtins that hints can use in sandbox mode. The functions are wrapped so that they don't
+/// give access to the `builtins` module through their `__self__`. `getattr`, `type`, `object`
+/// and the builtins needed to define classes are blocked instead, as they lead to every class
+/// of the process.
+const SAFE_BUILTINS: [&str; 57] = [
+    "abs",
+    "all",
+    "any",
+    "bin",
+    "bool",
+    "bytearray",
+    "bytes",
+    "callable",
+    "chr",
+    "dict",
+    "divmod",
+    "enumerate",
+    "filter",
+    "float",
+    "format",
+    "frozenset",
+    "hasattr",
+    "hash",
+    "hex",
+    "int",
+    "isinstance",
+    "issubclass",
+    "iter",
+    "len",
+    "list",
+    "map",
+    "max",
+    "min",
+    "next",
+    "oct",
+    "ord",
+    "pow",
+    "print",
+    "range",
+    "repr",
+    "reversed",
+    "round",
+    "set",
+    "slice",
+    "sorted",
+    "str",
+    "sum",
+    "tuple",
+    "zip",
+    "ArithmeticError",
+    "AssertionError",
+    "AttributeError",
+    "Exception",
+    "IndexError",
+    "KeyError",
+    "NotImplementedError",
+    "OverflowError",
+    "RuntimeError",
+    "StopIteration",
+    "TypeError",
+    "ValueError",
+    "ZeroDivisionError",
+];
+
+/// Attributes that hints can't access in sandbox mode, besides those starting with an underscore,
+/// as they lead to the frames of the callers and their unrestricted globals.
+const BLOCKED_ATTRIBUTES: [&str; 14] = [
+    "ag_code",
+    "ag_frame",
+    "cr_code",
+    "cr_frame",
+    "f_back",
+    "f_builtins",
+    "f_code",
+    "f_globals",
+    "f_locals",
+    "gi_code",
+    "gi_frame",
+    "mro",
+    "tb_frame",
+    "tb_next",
+];
+
+/// The only variables whose attributes hints can set or delete in sandbox mode, so that they can't
+/// change the modules and classes shared with the rest of the process. Hints can't rebind them.
+const WRITABLE_VARIABLES: [&str; 2] = ["ids", "cast"];
+
+/// AST nodes that bind a name, along with the field holding it, besides `ast.Name`.
+const BINDING_NODES: [(&str, &str); 8] = [
+    ("AsyncFunctionDef", "name"),
+    ("ExceptHandler", "name"),
+    ("FunctionDef", "name"),
+    ("MatchAs", "name"),
+    ("MatchMapping", "rest"),
+    ("MatchStar", "name"),
+    ("alias", "asname"),
+    ("arg", "arg"),
+];
+
+/// Builtins that raise `HintSandboxError` when a hint calls them in sandbox mode.
+const BLOCKED_BUILTINS: [&str; 20] = [
+    "__build_class__",
+    "breakpoint",
+    "compile",
+    "delattr",
+    "eval",
+    "exec",
+    "exit",
+    "getattr",
+    "globals",
+    "input",
+    "locals",
+    "object",
+    "open",
+    "property",
+    "quit",
+    "setattr",
+    "staticmethod",
+    "super",
+    "type",
+    "vars",
+];
+
+/// Restrictions on the Python hints run in sandbox mode: they only get a curated set of
+/// builtins, can only import the allowed modules, can't access the attributes that lead from an
+/// object to unrestricted modules (`__class__`, `__self__`, `__globals__`, frames...) and can only
+/// set attributes of `ids` and of `cast(...)`. This keeps hints away from the filesystem and the
+/// rest of the process. Modules are imported with `from module import name`: `import a.b` would
+/// give the unrestricted package `a`, and importing a module that isn't allowed from an allowed
+/// one fails too.
+#[derive(Debug, Clone)]
+pub(crate) struct Sandbox {
+    allowed_modules: Vec<String>,
+    /// The builtins of the hints, copied for each of them.
+    builtins: Option<Py<PyDict>>,
+    /// Sources of the hints that passed `check_code`.
+    checked_code: HashSet<String>,
+}
+
+impl Sandbox {
+    pub(crate) fn new(allowed_modules: Option<Vec<String>>) -> Sandbox {
+        Sandbox {
+            allowed_modules: allowed_modules.unwrap_or_else(|| {
+                DEFAULT_ALLOWED_MODULES
+                    .iter()
+                    .map(|module| module.to_string())
+                    .collect()
+            }),
+            builtins: None,
+            checked_code: HashSet::new(),
+        }
+    }
+
+    /// Returns the `__builtins__` of a hint run in the sandbox. Each hint gets its own copy, so
+    /// that changes made to it don't reach the next hints.
+    pub(crate) fn builtins<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        if let Some(builtins) = &self.builtins {
+            return builtins.as_ref(py).copy();
+        }
+        let builtins = self.build_builtins(py)?;
+        self.builtins = Some(builtins.into());
+        builtins.copy()
+    }
+
+    /// Fails with `HintSandboxError` if the code of a hint accesses a blocked attribute, sets or
+    /// deletes an attribute of anything but `ids` and `cast(...)`, or rebinds those.
+    pub(crate) fn check_code(&mut self, py: Python, code: &str) -> PyResult<()> {
+        if self.checked_code.contains(code) {
+            return Ok(());
+        }
+        let ast = py.import("ast")?;
+        let attribute_type = ast.getattr("Attribute")?;
+        let name_type = ast.getattr("Name")?;
+        let load_type = ast.getattr("Load")?;
+        for node in ast
+            .call_method1("walk", (ast.call_method1("parse", (code,))?,))?
+            .iter()?
+        {
+            let node = node?;
+            if node.is_instance(attribute_type)? {
+                let attribute = node.getattr("attr")?.extract::<&str>()?;
+                if attribute.starts_with('_') || BLOCKED_ATTRIBUTES.contains(&attribute) {
+                    return Err(HintSandboxError::new_err(format!(
+                        "Hint tried to access attribute '{attribute}', which is not allowed in sandbox mode."
+                    )));
+                }
+                if !node.getattr("ctx")?.is_instance(load_type)?
+                    && !is_writable(node, attribute_type, name_type)?
+                {
+                    return Err(HintSandboxError::new_err(format!(
+                        "Hint tried to set or delete attribute '{attribute}', which is only allowed for ids in sandbox mode."
+                    )));
+                }
+            }
+            if let Some(name) = bound_name(node, name_type, load_type)? {
+                if WRITABLE_VARIABLES.contains(&name) {
+                    return Err(HintSandboxError::new_err(format!(
+                        "Hint tried to rebind '{name}', which is not allowed in sandbox mode."
+                    )));
+                }
+            }
+        }
+        self.checked_code.insert(code.to_string());
+        Ok(())
+    }
+
+    fn build_builtins<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let builtins = py.import("builtins")?;
+        let sandbox_builtins = PyDict::new(py);
+        for name in SAFE_BUILTINS {
+            let builtin = builtins.getattr(name)?;
+            if builtin.is_instance_of::<PyType>()? {
+                sandbox_builtins.set_item(name, builtin)?;
+            } else {
+                let function = builtin.into();
+                sandbox_builtins
+                    .set_item(name, PyCell::new(py, PySandboxedBuiltin { function })?)?;
+            }
+        }
+        for name in BLOCKED_BUILTINS {
+            sandbox_builtins.set_item(name, PyCell::new(py, PyBlockedBuiltin { name })?)?;
+        }
+        sandbox_builtins.set_item(
+            "__import__",
+            PyCell::new(
+                py,
+                PySandboxImport {
+                    allowed_modules: self.allowed_modules.clone(),
+                },
+            )?,
+        )?;
+        Ok(sandbox_builtins)
+    }
+}
+
+/// Returns whether the target of an attribute assignment is reached from `ids` or `cast(...)`,
+/// as in `ids.x`, `ids.ptr[i].x` or `cast(ptr, "MyStruct*").x`.
+fn is_writable(target: &PyAny, attribute_type: &PyAny, name_type: &PyAny) -> PyResult<bool> {
+    let py = target.py();
+    let subscript_type = py.import("ast")?.getattr("Subscript")?;
+    let call_type = py.import("ast")?.getattr("Call")?;
+    let mut root = target.getattr("value")?;
+    while root.is_instance(attribute_type)? || root.is_instance(subscript_type)? {
+        root = root.getattr("value")?;
+    }
+    if root.is_instance(call_type)? {
+        root = root.getattr("func")?;
+    }
+    Ok(root.is_instance(name_type)?
+        && WRITABLE_VARIABLES.contains(&root.getattr("id")?.extract::<&str>()?))
+}
+
+/// Returns the name an AST node binds, if any, as an assignment target or a parameter does.
+fn bound_name<'py>(
+    node: &'py PyAny,
+    name_type: &PyAny,
+    load_type: &PyAny,
+) -> PyResult<Option<&'py str>> {
+    if node.is_instance(name_type)? {
+        if node.getattr("ctx")?.is_instance(load_type)? {
+            return Ok(None);
+        }
+        return Ok(Some(node.getattr("id")?.extract()?));
+    }
+    let node_type = node.get_type().name()?;
+    match BINDING_NODES.iter().find(|(name, _)| *name == node_type) {
+        Some((_, field)) => Ok(node.getattr(*field)?.extract()?),
+        None => Ok(None),
+    }
+}
+
+/// A builtin function as hints get it in sandbox mode: unlike the function itself, it has no
+/// `__self__` leading to the `builtins` module.
+#[pyclass(name = "SandboxedBuiltin")]
+struct PySandboxedBuiltin {
+    function: PyObject,
+}
+
+#[pymethods]
+impl PySandboxedBuiltin {
+    #[args(args = "*", kwargs = "**")]
+    fn __call__(&self, py: Python, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+        self.function.call(py, args, kwargs)
+    }
+}
+
+#[pyclass(name = "BlockedBuiltin")]
+struct PyBlockedBuiltin {
+    name: &'static str,
+}
+
+#[pymethods]
+impl PyBlockedBuiltin {
+    #[args(args = "*", kwargs = "**")]
+    fn __call__(&self, _args: &PyTuple, _kwargs: Option<&PyDict>) -> PyResult<()> {
+        Err(HintSandboxError::new_err(format!(
+            "Hint tried to call '{}', which is not allowed in sandbox mode.",
+            self.name
+        )))
+    }
+}
+
+/// The `__import__` of the sandbox, which only imports the allowed modules and their submodules.
+#[pyclass(name = "SandboxImport")]
+struct PySandboxImport {
+    allowed_modules: Vec<String>,
+}
+
+#[pymethods]
+impl PySandboxImport {
+    #[args(args = "*", kwargs = "**")]
+    fn __call__(
+        &self,
+        py: Python,
+        name: &str,
+        args: &PyTuple,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyObject> {
+        // Arguments after the name are globals, locals, fromlist and level
+        let level = match import_arg(args, kwargs, 3, "level") {
+            Some(level) => level.extract::<usize>()?,
+            None => 0,
+        };
+        let fromlist = match import_arg(args, kwargs, 2, "fromlist") {
+            Some(fromlist) if !fromlist.is_none() => fromlist.extract::<Vec<String>>()?,
+            _ => Vec::new(),
+        };
+        // `from package import module` only needs the imported modules to be allowed
+        let imports_allowed_submodules = !fromlist.is_empty()
+            && fromlist
+                .iter()
+                .all(|item| self.is_allowed(&format!("{name}.{item}")));
+        if level != 0 || !(self.is_allowed(name) || imports_allowed_submodules) {
+            return Err(import_error(name));
+        }
+        let mut import_args = vec![name.to_object(py)];
+        import_args.extend(args.iter().map(|arg| arg.to_object(py)));
+        let module = py
+            .import("builtins")?
+            .getattr("__import__")?
+            .call(PyTuple::new(py, import_args), kwargs)?;
+
+        // The imported names are read from the returned module, so none of them can be a module
+        // that isn't allowed, as `subprocess` in `from starkware.python.utils import subprocess`.
+        // Without names, as in `import a.b`, the returned module is the top-level package.
+        let module_type = py.import("types")?.getattr("ModuleType")?;
+        let module_name =
+            |module: &PyAny| -> PyResult<String> { module.getattr("__name__")?.extract() };
+        if fromlist.is_empty() {
+            let imported = module_name(module)?;
+            if !self.is_allowed(&imported) {
+                return Err(import_error(&imported));
+            }
+            return Ok(module.into());
+        }
+        let mut names = fromlist;
+        if names.iter().any(|item| item == "*") {
+            names = match module.getattr("__all__") {
+                Ok(all) => all.extract()?,
+                Err(_) => module
+                    .dir()
+                    .extract::<Vec<String>>()?
+                    .into_iter()
+                    .filter(|item| !item.starts_with('_'))
+                    .collect(),
+            };
+        }
+        for item in names {
+            if let Ok(value) = module.getattr(item.as_str()) {
+                if value.is_instance(module_type)? {
+                    let imported = module_name(value)?;
+                    if !self.is_allowed(&imported) {
+                        return Err(import_error(&imported));
+                    }
+                }
+            }
+        }
+        Ok(module.into())
+    }
+}
+
+impl PySandboxImport {
+    fn is_allowed(&self, name: &str) -> bool {
+        self.allowed_modules.iter().any(|module| {
+            name == module
+                || name
+                    .strip_prefix(module.as_str())
+                    .map_or(false, |submodule| submodule.starts_with('.'))
+        })
+    }
+}
+
+/// Returns an argument of `__import__` after the name, given by position or keyword.
+fn import_arg<'py>(
+    args: &'py PyTuple,
+    kwargs: Option<&'py PyDict>,
+    index: usize,
+    name: &str,
+) -> Option<&'py PyAny> {
+    kwargs
+        .and_then(|kwargs| kwargs.get_item(name))
+        .or_else(|| args.get_item(index).ok())
+}
+
+fn import_error(name: &str) -> PyErr {
+    HintSandboxError::new_err(format!(
+        "Hint tried to import '{name}', which is not allowed in sandbox mode."
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_in_sandbox(py: Python, code: &str) -> PyResult<()> {
+        let mut sandbox = Sandbox::new(None);
+        sandbox.check_code(py, code)?;
+        let globals = PyDict::new(py);
+        globals
+            .set_item("__builtins__", sandbox.builtins(py)?)
+            .unwrap();
+        py.run(code, Some(globals), None)
+    }
+
+    #[test]
+    fn sandbox_allows_safe_code() {
+        Python::with_gil(|py| {
+            assert!(run_in_sandbox(py, "import math; assert math.sqrt(4) == 2").is_ok());
+            assert!(run_in_sandbox(py, "from math import gcd; assert gcd(4, 6) == 2").is_ok());
+            assert!(run_in_sandbox(py, "x = sum(range(3)); assert len([x]) == 1").is_ok());
+            assert!(run_in_sandbox(py, "print(int.from_bytes(b'\\x01', 'little'))").is_ok());
+            assert!(Sandbox::new(None)
+                .check_code(py, "ids.x = 1; ids.a[0].b += 2; cast(p, 'T*').y = 3")
+                .is_ok());
+        });
+    }
+
+    #[test]
+    fn sandbox_blocks_escapes() {
+        Python::with_gil(|py| {
+            for code in [
+                "import os",
+                "import mathematics",
+                "from . import math",
+                "__import__('subprocess')",
+                "open('/etc/passwd')",
+                "eval('1')",
+                "len.__self__.__import__('os')",
+                "print.__self__.open('/etc/passwd')",
+                "().__class__.__base__.__subclasses__()",
+                "getattr((), '__class__')",
+                "type(())",
+                "object.__subclasses__()",
+                "class A: pass",
+                "g = (x for x in [1]); g.gi_frame.f_back",
+                "import math; setattr(math, 'sqrt', abs)",
+                "import math; math.sqrt = abs",
+                "import math; del math.pi",
+                "ids = 1",
+                "def f(ids): ids.x = 1",
+            ] {
+                assert!(
+                    run_in_sandbox(py, code)
+                        .unwrap_err()
+                        .is_instance_of::<HintSandboxError>(py),
+                    "{code}"
+                );
+            }
+
+            // `json` imports `codecs`, which isn't allowed
+            let mut sandbox = Sandbox::new(Some(vec![String::from("json")]));
+            let globals = PyDict::new(py);
+            globals
+                .set_item("__builtins__", sandbox.builtins(py).unwrap())
+                .unwrap();
+            assert!(py
+                .run("from json import dumps, decoder", Some(globals), None)
+                .is_ok());
+            assert!(py
+                .run("from json import codecs", Some(globals), None)
+                .unwrap_err()
+                .is_instance_of::<HintSandboxError>(py));
+        });
+    }
+
+    #[test]
+    fn is_allowed_module() {
+        let import = PySandboxImport {
+            allowed_modules: vec![String::from("starkware")],
+        };
+        assert!(import.is_allowed("starkware"));
+        assert!(import.is_allowed("starkware.cairo.common.math_utils"));
+        assert!(!import.is_allowed("starkware_fake"));
+        assert!(!import.is_allowed("os"));
+    }
+}
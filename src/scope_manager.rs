@@ -1,9 +1,251 @@
-use std::{any::Any, collections::HashMap};
+use std::{any::Any, cell::RefCell, collections::HashMap, rc::Rc};
 
-use cairo_rs::{any_box, types::exec_scope::ExecutionScopes};
-use pyo3::{pyclass, pymethods, PyErr, PyObject};
+use cairo_rs::{
+    any_box,
+    hint_processor::builtin_hint_processor::dict_manager::DictManager,
+    types::{
+        exec_scope::ExecutionScopes,
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
+};
+use num_bigint::BigInt;
+use pyo3::{
+    exceptions::PyValueError,
+    pyclass, pymethods,
+    types::{PyDict, PyLong},
+    IntoPy, PyAny, PyErr, PyObject, PyResult, PyTypeInfo, Python, ToPyObject,
+};
 
-use crate::utils::to_py_error;
+use crate::{
+    relocatable::{PyMaybeRelocatable, PyRelocatable},
+    utils::to_py_error,
+};
+
+macro_rules! clone_if_downcasts {
+    ($value:expr, $($type:ty),*) => {
+        $(
+            if let Some(value) = $value.downcast_ref::<$type>() {
+                return Some(any_box!(value.clone()));
+            }
+        )*
+    };
+}
+
+macro_rules! to_py_if_downcasts {
+    ($value:expr, $py:expr, $($type:ty),*) => {
+        $(
+            if let Some(value) = $value.downcast_ref::<$type>() {
+                return Some(value.to_object($py));
+            }
+        )*
+    };
+}
+
+macro_rules! extract_if_same_type {
+    ($previous:expr, $value:expr, $($type:ty),*) => {
+        $(
+            if $previous.is::<$type>() {
+                if let Ok(value) = $value.extract::<$type>() {
+                    return any_box!(value);
+                }
+            }
+        )*
+    };
+}
+
+/// Converts a scope variable into the Python object seen by Python hints. Returns None for the
+/// types that aren't shared with them, such as the `DictManager`, which is exposed as
+/// `__dict_manager` instead.
+pub(crate) fn scope_variable_to_py(py: Python, value: &dyn Any) -> Option<PyObject> {
+    to_py_if_downcasts!(
+        value,
+        py,
+        PyObject,
+        BigInt,
+        usize,
+        bool,
+        Vec<BigInt>,
+        Vec<usize>,
+        HashMap<BigInt, BigInt>,
+        HashMap<BigInt, Vec<BigInt>>,
+        HashMap<BigInt, Vec<usize>>
+    );
+    if let Some(value) = value.downcast_ref::<Relocatable>() {
+        return Some(PyRelocatable::from(*value).into_py(py));
+    }
+    if let Some(value) = value.downcast_ref::<MaybeRelocatable>() {
+        return Some(PyMaybeRelocatable::from(value).to_object(py));
+    }
+    if let Some(values) = value.downcast_ref::<Vec<Relocatable>>() {
+        return Some(
+            values
+                .iter()
+                .map(|value| PyRelocatable::from(*value))
+                .collect::<Vec<_>>()
+                .into_py(py),
+        );
+    }
+    if let Some(values) = value.downcast_ref::<Vec<MaybeRelocatable>>() {
+        return Some(
+            values
+                .iter()
+                .map(PyMaybeRelocatable::from)
+                .collect::<Vec<_>>()
+                .to_object(py),
+        );
+    }
+    None
+}
+
+/// Converts a value assigned by a Python hint into a scope variable. If it replaces a variable
+/// of a type shared with Python hints, that type is kept when possible, so that builtin hints
+/// can still read it. Otherwise, ints are stored as `BigInt`s, the type builtin hints read
+/// integers as, and any other value as a Python object.
+pub(crate) fn scope_variable_from_py(value: &PyAny, previous: Option<&dyn Any>) -> Box<dyn Any> {
+    if let Some(previous) = previous {
+        extract_if_same_type!(
+            previous,
+            value,
+            BigInt,
+            usize,
+            bool,
+            Vec<BigInt>,
+            Vec<usize>,
+            HashMap<BigInt, BigInt>,
+            HashMap<BigInt, Vec<BigInt>>,
+            HashMap<BigInt, Vec<usize>>
+        );
+        if previous.is::<Relocatable>() {
+            if let Ok(value) = value.extract::<PyRelocatable>() {
+                return any_box!(Relocatable::from(&value));
+            }
+        }
+        if previous.is::<MaybeRelocatable>() {
+            if let Ok(value) = value.extract::<PyMaybeRelocatable>() {
+                return any_box!(MaybeRelocatable::from(value));
+            }
+        }
+        if previous.is::<Vec<Relocatable>>() {
+            if let Ok(values) = value.extract::<Vec<PyRelocatable>>() {
+                return any_box!(values.iter().map(Relocatable::from).collect::<Vec<_>>());
+            }
+        }
+        if previous.is::<Vec<MaybeRelocatable>>() {
+            if let Ok(values) = value.extract::<Vec<PyMaybeRelocatable>>() {
+                return any_box!(values
+                    .into_iter()
+                    .map(MaybeRelocatable::from)
+                    .collect::<Vec<_>>());
+            }
+        }
+    }
+    if PyLong::is_exact_type_of(value) {
+        if let Ok(value) = value.extract::<BigInt>() {
+            return any_box!(value);
+        }
+    }
+    any_box!(value.to_object(value.py()))
+}
+
+/// Assigns a value set by a Python hint to a variable of the current scope.
+pub(crate) fn assign_scope_variable_from_py(
+    exec_scopes: &mut ExecutionScopes,
+    name: &str,
+    value: &PyAny,
+) {
+    let previous = exec_scopes
+        .data
+        .last()
+        .and_then(|scope| scope.get(name))
+        .map(|previous| previous.as_ref());
+    let value = scope_variable_from_py(value, previous);
+    exec_scopes.assign_or_update_variable(name, value);
+}
+
+/// Returns a deep copy of a Python object, or the object itself when it can't be copied
+/// (modules, locks and the like are shared between the original and the copy).
+pub(crate) fn deepcopy(py: Python, pyobj: &PyObject) -> PyObject {
+    deepcopy_with_memo(py, pyobj, PyDict::new(py))
+}
+
+/// Same as `deepcopy`, with the `memo` of `copy.deepcopy`, which maps the objects already copied
+/// to their copies.
+fn deepcopy_with_memo(py: Python, pyobj: &PyObject, memo: &PyDict) -> PyObject {
+    py.import("copy")
+        .and_then(|copy| copy.call_method1("deepcopy", (pyobj, memo)))
+        .map(Into::into)
+        .unwrap_or_else(|_| pyobj.clone_ref(py))
+}
+
+/// The copies made while copying a scope stack, so that the values shared between scopes are
+/// still shared in the copy.
+struct SharedCopies<'py> {
+    /// The `memo` of `copy.deepcopy`.
+    memo: &'py PyDict,
+    dict_managers: HashMap<*const RefCell<DictManager>, Rc<RefCell<DictManager>>>,
+}
+
+/// Returns an independent copy of a scope variable, or None if its type isn't known.
+fn copy_scope_variable(
+    py: Python,
+    value: &dyn Any,
+    copies: &mut SharedCopies,
+) -> Option<Box<dyn Any>> {
+    if let Some(pyobj) = value.downcast_ref::<PyObject>() {
+        return Some(any_box!(deepcopy_with_memo(py, pyobj, copies.memo)));
+    }
+    if let Some(dict_manager) = value.downcast_ref::<Rc<RefCell<DictManager>>>() {
+        let copy = copies
+            .dict_managers
+            .entry(Rc::as_ptr(dict_manager))
+            .or_insert_with(|| Rc::new(RefCell::new(dict_manager.borrow().clone())));
+        return Some(any_box!(Rc::clone(copy)));
+    }
+    clone_if_downcasts!(
+        value,
+        BigInt,
+        usize,
+        bool,
+        Relocatable,
+        MaybeRelocatable,
+        Vec<BigInt>,
+        Vec<usize>,
+        Vec<Relocatable>,
+        Vec<MaybeRelocatable>,
+        HashMap<BigInt, BigInt>,
+        HashMap<BigInt, Vec<BigInt>>,
+        HashMap<BigInt, Vec<usize>>
+    );
+    None
+}
+
+/// Copies the whole scope stack, so that the copy isn't affected by later hints. Values shared
+/// between scopes, such as the dict manager, are shared between the same scopes of the copy.
+pub(crate) fn copy_exec_scopes(
+    py: Python,
+    exec_scopes: &ExecutionScopes,
+) -> PyResult<ExecutionScopes> {
+    let mut copies = SharedCopies {
+        memo: PyDict::new(py),
+        dict_managers: HashMap::new(),
+    };
+    let mut data = Vec::new();
+    for scope in exec_scopes.data.iter() {
+        let mut new_scope = HashMap::<String, Box<dyn Any>>::new();
+        for (name, value) in scope {
+            let value = copy_scope_variable(py, value.as_ref(), &mut copies).ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "Scope variable '{name}' has a type that can't be copied"
+                ))
+            })?;
+            new_scope.insert(name.to_string(), value);
+        }
+        data.push(new_scope);
+    }
+    let mut copy = ExecutionScopes::new();
+    copy.data = data;
+    Ok(copy)
+}
 
 #[pyclass(unsendable)]
 #[derive(Debug, Clone)]
@@ -22,7 +264,8 @@ impl PyEnterScope {
         for scope_variables in self.new_scopes.iter() {
             let mut new_scope = HashMap::<String, Box<dyn Any>>::new();
             for (name, pyobj) in scope_variables {
-                new_scope.insert(name.to_string(), any_box!(pyobj.clone()));
+                let value = Python::with_gil(|py| scope_variable_from_py(pyobj.as_ref(py), None));
+                new_scope.insert(name.to_string(), value);
             }
             scopes.enter_scope(new_scope);
         }
@@ -77,3 +320,112 @@ impl PyExitScope {
         self.num += 1
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cairo_rs::bigint;
+    use pyo3::ToPyObject;
+
+    #[test]
+    fn copy_exec_scopes_is_independent() {
+        Python::with_gil(|py| {
+            let mut exec_scopes = ExecutionScopes::new();
+            let list = py.eval("[1, 2]", None, None).unwrap().to_object(py);
+            exec_scopes.assign_or_update_variable("list", any_box!(list.clone_ref(py)));
+            exec_scopes.assign_or_update_variable("n", any_box!(bigint!(3)));
+            exec_scopes.enter_scope(HashMap::new());
+
+            let copy = copy_exec_scopes(py, &exec_scopes).unwrap();
+            list.call_method1(py, "append", (3,)).unwrap();
+
+            assert_eq!(copy.data.len(), 2);
+            let copied_list = copy.data[0]["list"].downcast_ref::<PyObject>().unwrap();
+            assert_eq!(copied_list.extract::<Vec<usize>>(py).unwrap(), vec![1, 2]);
+            assert_eq!(
+                copy.data[0]["n"].downcast_ref::<BigInt>(),
+                Some(&bigint!(3))
+            );
+        });
+    }
+
+    #[test]
+    fn copy_exec_scopes_keeps_sharing_between_scopes() {
+        Python::with_gil(|py| {
+            let mut exec_scopes = ExecutionScopes::new();
+            let dict_manager = Rc::new(RefCell::new(DictManager::new()));
+            let list = py.eval("[1]", None, None).unwrap().to_object(py);
+            exec_scopes.assign_or_update_variable("dict_manager", any_box!(dict_manager.clone()));
+            exec_scopes.assign_or_update_variable("list", any_box!(list.clone_ref(py)));
+            exec_scopes.enter_scope(HashMap::from([
+                (
+                    String::from("dict_manager"),
+                    any_box!(Rc::clone(&dict_manager)),
+                ),
+                (String::from("list"), any_box!(list)),
+            ]));
+
+            let copy = copy_exec_scopes(py, &exec_scopes).unwrap();
+            let dict_manager_in = |depth: usize| {
+                copy.data[depth]["dict_manager"]
+                    .downcast_ref::<Rc<RefCell<DictManager>>>()
+                    .unwrap()
+            };
+            assert!(Rc::ptr_eq(dict_manager_in(0), dict_manager_in(1)));
+            assert!(!Rc::ptr_eq(dict_manager_in(0), &dict_manager));
+
+            let list_in =
+                |depth: usize| copy.data[depth]["list"].downcast_ref::<PyObject>().unwrap();
+            list_in(1).call_method1(py, "append", (2,)).unwrap();
+            assert_eq!(list_in(0).extract::<Vec<usize>>(py).unwrap(), vec![1, 2]);
+        });
+    }
+
+    #[test]
+    fn scope_variables_round_trip() {
+        Python::with_gil(|py| {
+            let values: Vec<Box<dyn Any>> = vec![
+                any_box!(bigint!(7)),
+                any_box!(3_usize),
+                any_box!(Relocatable::from((1, 2))),
+                any_box!(vec![
+                    MaybeRelocatable::from(bigint!(1)),
+                    MaybeRelocatable::from((2, 0))
+                ]),
+                any_box!(HashMap::from([(bigint!(1), vec![bigint!(2)])])),
+            ];
+            for value in values {
+                let pyobj = scope_variable_to_py(py, value.as_ref()).unwrap();
+                let converted = scope_variable_from_py(pyobj.as_ref(py), Some(value.as_ref()));
+                assert_eq!(
+                    (*converted).type_id(),
+                    (*value).type_id(),
+                    "{}",
+                    pyobj.as_ref(py)
+                );
+            }
+
+            // Values that don't fit the previous type are kept as Python objects
+            let n: Box<dyn Any> = any_box!(3_usize);
+            let converted =
+                scope_variable_from_py(py.eval("'three'", None, None).unwrap(), Some(n.as_ref()));
+            assert!(converted.is::<PyObject>());
+            let converted = scope_variable_from_py(py.eval("3", None, None).unwrap(), None);
+            assert_eq!(converted.downcast_ref::<BigInt>(), Some(&bigint!(3)));
+            let converted = scope_variable_from_py(py.eval("True", None, None).unwrap(), None);
+            assert!(converted.is::<PyObject>());
+
+            assert!(scope_variable_to_py(py, &Rc::new(RefCell::new(DictManager::new()))).is_none());
+        });
+    }
+
+    #[test]
+    fn copy_exec_scopes_unsupported_type() {
+        Python::with_gil(|py| {
+            let mut exec_scopes = ExecutionScopes::new();
+            exec_scopes.assign_or_update_variable("unknown", any_box!(1_u8));
+
+            assert!(copy_exec_scopes(py, &exec_scopes).is_err());
+        });
+    }
+}
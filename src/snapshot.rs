@@ -0,0 +1,25 @@
+use crate::run_context::PyRunContext;
+use cairo_rs::{
+    types::{exec_scope::ExecutionScopes, relocatable::Relocatable},
+    vm::vm_core::VirtualMachine,
+};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+/// The state of a `CairoRunner` at a given point of its execution, as returned by
+/// `CairoRunner.snapshot()`. It can be restored any number of times.
+#[pyclass(unsendable, name = "RunnerSnapshot")]
+pub struct PyRunnerSnapshot {
+    pub(crate) vm: VirtualMachine,
+    pub(crate) exec_scopes: ExecutionScopes,
+    pub(crate) hint_locals: HashMap<String, PyObject>,
+    pub(crate) end: Option<Relocatable>,
+}
+
+#[pymethods]
+impl PyRunnerSnapshot {
+    #[getter]
+    pub fn run_context(&self) -> PyRunContext {
+        PyRunContext::new(*self.vm.get_pc(), self.vm.get_ap(), self.vm.get_fp())
+    }
+}
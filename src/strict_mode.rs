@@ -0,0 +1,94 @@
+use crate::relocatable::PyRelocatable;
+use cairo_rs::types::relocatable::Relocatable;
+use pyo3::{create_exception, exceptions::PyException, prelude::*, types::PyBytes};
+use std::collections::HashSet;
+
+create_exception!(cairo_rs_py, UnauthorizedHint, PyException);
+
+/// The hints that may run as Python code when the runner is in strict mode, given by their code
+/// or by the SHA-256 hex digest of it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HintAllowlist {
+    allowed: HashSet<String>,
+    /// Code of the hints allowed by their digest, which doesn't need to be hashed again.
+    digest_matches: HashSet<String>,
+}
+
+impl HintAllowlist {
+    pub(crate) fn new(allowed_hints: Vec<String>) -> HintAllowlist {
+        HintAllowlist {
+            allowed: allowed_hints.into_iter().collect(),
+            digest_matches: HashSet::new(),
+        }
+    }
+
+    /// Fails with `UnauthorizedHint`, carrying `pc` and `code`, if the hint isn't allowed.
+    pub(crate) fn check(&mut self, py: Python, pc: Relocatable, code: &str) -> PyResult<()> {
+        if self.allowed.contains(code) || self.digest_matches.contains(code) {
+            return Ok(());
+        }
+        if self.allowed.contains(&sha256_hex_digest(py, code)?) {
+            self.digest_matches.insert(code.to_string());
+            return Ok(());
+        }
+
+        let pc = PyRelocatable::from(pc);
+        let error = UnauthorizedHint::new_err(format!(
+            "Hint at pc {} is not allowed in strict mode:\n{code}",
+            pc.__repr__()
+        ));
+        let value = error.value(py);
+        value.setattr("pc", pc.into_py(py))?;
+        value.setattr("source", code)?;
+        Err(error)
+    }
+}
+
+fn sha256_hex_digest(py: Python, code: &str) -> PyResult<String> {
+    py.import("hashlib")?
+        .call_method1("sha256", (PyBytes::new(py, code.as_bytes()),))?
+        .call_method0("hexdigest")?
+        .extract()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_allowed_hints() {
+        let mut allowlist = HintAllowlist::new(vec![
+            String::from("print(ap)"),
+            // SHA-256 of "x = 1"
+            String::from("8ff436def1451285599a1b1ad70800493b8dcafde2912e1a38345633054e4c26"),
+        ]);
+        Python::with_gil(|py| {
+            let pc = Relocatable::from((0, 3));
+            assert!(allowlist.check(py, pc, "print(ap)").is_ok());
+            assert!(allowlist.check(py, pc, "x = 1").is_ok());
+            assert!(allowlist.digest_matches.contains("x = 1"));
+            assert!(!allowlist.allowed.contains("x = 1"));
+            assert!(allowlist.check(py, pc, "x = 1").is_ok());
+
+            let error = allowlist.check(py, pc, "import os").unwrap_err();
+            assert!(error.is_instance_of::<UnauthorizedHint>(py));
+            let value = error.value(py);
+            assert_eq!(
+                value
+                    .getattr("pc")
+                    .unwrap()
+                    .extract::<PyRelocatable>()
+                    .unwrap(),
+                PyRelocatable::from((0, 3))
+            );
+            assert_eq!(
+                value
+                    .getattr("source")
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "import os"
+            );
+        });
+    }
+}
@@ -8,6 +8,7 @@ macro_rules! pycell {
         PyCell::new($py, $val)?
     };
 }
+
 pub fn to_py_error<T: Display>(error: T) -> PyErr {
     PyValueError::new_err(error.to_string())
 }
@@ -1,43 +1,113 @@
+use crate::dict_manager::PyDictManager;
 use crate::ecdsa::PySignature;
-use crate::ids::PyIds;
+use crate::ids::{ids_cells, PyCast, PyIds};
 use crate::pycell;
 use crate::run_context::PyRunContext;
-use crate::scope_manager::{PyEnterScope, PyExitScope};
+use crate::sandbox::Sandbox;
+use crate::scope_manager::{
+    assign_scope_variable_from_py, scope_variable_to_py, PyEnterScope, PyExitScope,
+};
+use crate::strict_mode::HintAllowlist;
 use crate::to_felt_or_relocatable::ToFeltOrRelocatableFunc;
 use crate::utils::to_py_error;
+use crate::watchpoints::AccessLog;
 use crate::{
-    memory::PyMemory, memory_segments::PySegmentManager, range_check::PyRangeCheck,
+    memory::{convert_rejected_writes, write_memory, PyMemory},
+    memory_segments::PySegmentManager,
+    range_check::PyRangeCheck,
     relocatable::PyRelocatable,
 };
 use cairo_rs::any_box;
 use cairo_rs::hint_processor::hint_processor_definition::HintProcessor;
 use cairo_rs::serde::deserialize_program::{Attribute, Member};
 use cairo_rs::types::exec_scope::ExecutionScopes;
+use cairo_rs::types::relocatable::Relocatable;
 use cairo_rs::vm::vm_core::VirtualMachine;
 use cairo_rs::{
     hint_processor::builtin_hint_processor::builtin_hint_processor_definition::HintProcessorData,
     vm::errors::vm_errors::VirtualMachineError,
 };
+use lazy_static::lazy_static;
 use num_bigint::BigInt;
-use pyo3::{pyclass, pymethods, PyObject, ToPyObject};
+use pyo3::{pyclass, pymethods, Py, PyObject, ToPyObject};
 use pyo3::{types::PyDict, Python};
 use pyo3::{PyCell, PyErr};
 use std::any::Any;
-use std::collections::HashMap;
-use std::{cell::RefCell, rc::Rc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+/// Number of code objects kept by `COMPILED_HINT_CODES`.
+const COMPILED_HINT_CODES_CAPACITY: usize = 4096;
 
-const GLOBAL_NAMES: [&str; 18] = [
+lazy_static! {
+    /// Code objects of the hints compiled by any runner, by source.
+    static ref COMPILED_HINT_CODES: Mutex<HintCodeCache> =
+        Mutex::new(HintCodeCache::new(COMPILED_HINT_CODES_CAPACITY));
+}
+
+/// Code objects by hint source, dropping the least recently used ones beyond `capacity`, so
+/// that loading many programs doesn't keep all their hints alive.
+struct HintCodeCache {
+    capacity: usize,
+    /// Code objects along with the time of their last use.
+    code_objects: HashMap<String, (PyObject, u64)>,
+    /// Sources by the time of their last use, oldest first.
+    uses: BTreeMap<u64, String>,
+    time: u64,
+}
+
+impl HintCodeCache {
+    fn new(capacity: usize) -> HintCodeCache {
+        HintCodeCache {
+            capacity,
+            code_objects: HashMap::new(),
+            uses: BTreeMap::new(),
+            time: 0,
+        }
+    }
+
+    fn get(&mut self, py: Python, code: &str) -> Option<PyObject> {
+        self.time += 1;
+        let (code_object, last_use) = self.code_objects.get_mut(code)?;
+        if let Some(code) = self.uses.remove(last_use) {
+            self.uses.insert(self.time, code);
+        }
+        *last_use = self.time;
+        Some(code_object.clone_ref(py))
+    }
+
+    fn insert(&mut self, code: String, code_object: PyObject) {
+        self.time += 1;
+        if let Some((_, last_use)) = self.code_objects.get(&code) {
+            self.uses.remove(last_use);
+        } else if self.code_objects.len() >= self.capacity {
+            if let Some((_, least_recently_used)) = self.uses.pop_first() {
+                self.code_objects.remove(&least_recently_used);
+            }
+        }
+        self.uses.insert(self.time, code.clone());
+        self.code_objects.insert(code, (code_object, self.time));
+    }
+}
+
+const GLOBAL_NAMES: [&str; 20] = [
     "memory",
     "segments",
     "ap",
     "fp",
     "ids",
+    "cast",
     "vm_enter_scope",
     "vm_exit_scope",
     "to_felt_or_relocatable",
     "range_check_builtin",
     "ecdsa_builtin",
     "PRIME",
+    "__dict_manager",
     "__doc__",
     "__annotations__",
     "__package__",
@@ -52,12 +122,25 @@ const GLOBAL_NAMES: [&str; 18] = [
 pub struct PyVM {
     pub(crate) vm: Rc<RefCell<VirtualMachine>>,
     pub(crate) failed_hint_index: Option<usize>,
+    /// Code objects of the hints run so far, by pc offset and hint index, along with their source.
+    compiled_hints: HashMap<(usize, usize), (String, PyObject)>,
+    hint_globals: Option<Py<PyDict>>,
+    /// Python callables that implement the hints with the given code, instead of running it.
+    pub(crate) registered_hints: HashMap<String, PyObject>,
+    /// When set, only the hints it allows can run as Python code.
+    pub(crate) hint_allowlist: Option<HintAllowlist>,
+    sandbox: Option<Sandbox>,
+    pub(crate) access_log: AccessLog,
+    /// Set by `memory.freeze()`, after which the memory can't be written.
+    pub(crate) memory_frozen: Rc<Cell<bool>>,
+    /// Set once `memory.add_validation_rule` is called, as the rules run Python code on writes.
+    pub(crate) has_py_validation_rules: Rc<Cell<bool>>,
 }
 
 #[pymethods]
 impl PyVM {
     #[getter]
-    fn run_context(&self) -> PyRunContext {
+    pub(crate) fn run_context(&self) -> PyRunContext {
         let vm = self.vm.borrow();
         PyRunContext::new(*vm.get_pc(), vm.get_ap(), vm.get_fp())
     }
@@ -76,6 +159,30 @@ impl PyVM {
                 error_message_attributes,
             ))),
             failed_hint_index: None,
+            compiled_hints: HashMap::new(),
+            hint_globals: None,
+            registered_hints: HashMap::new(),
+            hint_allowlist: None,
+            sandbox: None,
+            access_log: Rc::new(RefCell::new(None)),
+            memory_frozen: Rc::new(Cell::new(false)),
+            has_py_validation_rules: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// Returns a `PyVM` over the same VM, without the hint caches, to be handed to Python code.
+    fn share(&self) -> PyVM {
+        PyVM {
+            vm: Rc::clone(&self.vm),
+            failed_hint_index: None,
+            compiled_hints: HashMap::new(),
+            hint_globals: None,
+            registered_hints: HashMap::new(),
+            hint_allowlist: None,
+            sandbox: None,
+            access_log: Rc::clone(&self.access_log),
+            memory_frozen: Rc::clone(&self.memory_frozen),
+            has_py_validation_rules: Rc::clone(&self.has_py_validation_rules),
         }
     }
 
@@ -83,20 +190,48 @@ impl PyVM {
         Rc::clone(&self.vm)
     }
 
+    pub(crate) fn is_sandboxed(&self) -> bool {
+        self.sandbox.is_some()
+    }
+
+    /// Sets the restrictions of the Python hints run from now on, if any.
+    pub(crate) fn set_sandbox(&mut self, sandbox: Option<Sandbox>) {
+        self.sandbox = sandbox;
+        self.hint_globals = None;
+    }
+
+    /// Runs `hint_data`, the `hint_index`-th hint at the current pc, as Python code, or through
+    /// the callable registered for its code.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn execute_hint(
         &mut self,
         hint_data: &HintProcessorData,
+        hint_index: usize,
         hint_locals: &mut HashMap<String, PyObject>,
         exec_scopes: &mut ExecutionScopes,
         constants: &HashMap<String, BigInt>,
         struct_types: Rc<HashMap<String, HashMap<String, Member>>>,
         static_locals: Option<&HashMap<String, PyObject>>,
     ) -> Result<(), PyErr> {
+        if let Some(func) = self.registered_hints.get(&hint_data.code) {
+            return self.execute_registered_hint(
+                func,
+                hint_data,
+                exec_scopes,
+                constants,
+                struct_types,
+            );
+        }
+        if let Some(hint_allowlist) = &mut self.hint_allowlist {
+            let pc = *self.vm.borrow().get_pc();
+            Python::with_gil(|py| hint_allowlist.check(py, pc, &hint_data.code))?;
+        }
+
         Python::with_gil(|py| -> Result<(), PyErr> {
-            let memory = PyMemory::new(self);
-            let segments = PySegmentManager::new(self, memory.clone());
+            let globals = self.hint_globals(py)?;
             let ap = PyRelocatable::from((*self.vm).borrow().get_ap());
             let fp = PyRelocatable::from((*self.vm).borrow().get_fp());
+            let cast = PyCast::new(self, Rc::clone(&struct_types));
             let ids = PyIds::new(
                 self,
                 &hint_data.ids_data,
@@ -106,31 +241,19 @@ impl PyVM {
             );
             let enter_scope = pycell!(py, PyEnterScope::new());
             let exit_scope = pycell!(py, PyExitScope::new());
-            let range_check_builtin =
-                PyRangeCheck::from((*self.vm).borrow().get_range_check_builtin());
             let ecdsa_builtin = pycell!(py, PySignature::new());
-            let prime = (*self.vm).borrow().get_prime().clone();
-            let to_felt_or_relocatable = ToFeltOrRelocatableFunc;
-
-            // This line imports Python builtins. If not imported, this will run only with Python 3.10
-            let globals = py.import("__main__")?.dict().copy()?;
 
             add_scope_locals(globals, exec_scopes)?;
+            let dict_manager = pycell!(py, PyDictManager::from_scope(exec_scopes, self.get_vm()));
 
-            globals.set_item("memory", pycell!(py, memory))?;
-            globals.set_item("segments", pycell!(py, segments))?;
+            globals.set_item("__dict_manager", dict_manager)?;
             globals.set_item("ap", pycell!(py, ap))?;
             globals.set_item("fp", pycell!(py, fp))?;
             globals.set_item("ids", pycell!(py, ids))?;
+            globals.set_item("cast", pycell!(py, cast))?;
             globals.set_item("vm_enter_scope", enter_scope)?;
             globals.set_item("vm_exit_scope", exit_scope)?;
-            globals.set_item("range_check_builtin", range_check_builtin)?;
             globals.set_item("ecdsa_builtin", ecdsa_builtin)?;
-            globals.set_item("PRIME", prime)?;
-            globals.set_item(
-                "to_felt_or_relocatable",
-                pycell!(py, to_felt_or_relocatable),
-            )?;
 
             for (name, pyobj) in hint_locals.iter() {
                 globals.set_item(name, pyobj)?;
@@ -142,9 +265,16 @@ impl PyVM {
                 }
             }
 
-            py.run(&hint_data.code, Some(globals), None)?;
+            if let Some(sandbox) = &mut self.sandbox {
+                sandbox.check_code(py, &hint_data.code)?;
+            }
+            let code = self.compiled_hint_code(py, hint_index, &hint_data.code)?;
+            py.import("builtins")?
+                .getattr("exec")?
+                .call1((code, globals))?;
 
             update_scope_hint_locals(exec_scopes, hint_locals, static_locals, globals, py);
+            dict_manager.borrow().add_to_scope(exec_scopes);
 
             if self.vm.borrow_mut().get_signature_builtin().is_ok() {
                 ecdsa_builtin
@@ -164,6 +294,7 @@ impl PyVM {
         Ok(())
     }
 
+    /// Runs the hints at the current pc, returning how many of them were run as Python code.
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn step_hint(
         &mut self,
@@ -174,8 +305,9 @@ impl PyVM {
         struct_types: Rc<HashMap<String, HashMap<String, Member>>>,
         constants: &HashMap<String, BigInt>,
         static_locals: Option<&HashMap<String, PyObject>>,
-    ) -> Result<(), PyErr> {
+    ) -> Result<usize, PyErr> {
         let pc_offset = (*self.vm).borrow().get_pc().offset;
+        let mut py_hints_run = 0;
 
         if let Some(hint_list) = hint_data_dictionary.get(&pc_offset) {
             for (hint_index, hint_data) in hint_list.iter().enumerate() {
@@ -190,6 +322,7 @@ impl PyVM {
 
                     if let Err(hint_error) = self.execute_hint(
                         hint_data,
+                        hint_index,
                         hint_locals,
                         exec_scopes,
                         constants,
@@ -199,15 +332,18 @@ impl PyVM {
                         self.failed_hint_index = Some(hint_index);
                         return Err(hint_error);
                     }
+                    py_hints_run += 1;
                 }
             }
         }
 
-        Ok(())
+        Ok(py_hints_run)
     }
 
+    /// Runs the hints of the current pc, returning how many were run as Python code. A step runs
+    /// them, then `run_instruction`.
     #[allow(clippy::too_many_arguments)]
-    pub(crate) fn step(
+    pub(crate) fn run_hints(
         &mut self,
         hint_executor: &mut dyn HintProcessor,
         hint_locals: &mut HashMap<String, PyObject>,
@@ -216,17 +352,145 @@ impl PyVM {
         struct_types: Rc<HashMap<String, HashMap<String, Member>>>,
         constants: &HashMap<String, BigInt>,
         static_locals: Option<&HashMap<String, PyObject>>,
+    ) -> Result<usize, PyErr> {
+        convert_rejected_writes(|| {
+            self.step_hint(
+                hint_executor,
+                hint_locals,
+                exec_scopes,
+                hint_data_dictionary,
+                struct_types,
+                constants,
+                static_locals,
+            )
+        })
+    }
+
+    /// Executes the instruction at the current pc, once its hints have run.
+    pub(crate) fn run_instruction(&mut self) -> Result<(), PyErr> {
+        write_memory(|| self.vm.borrow_mut().step_instruction())
+    }
+
+    /// Returns the cells of the ids of the hints at the current pc.
+    pub(crate) fn hint_ids_cells(
+        &self,
+        hint_data_dictionary: &HashMap<usize, Vec<Box<dyn Any>>>,
+        struct_types: &HashMap<String, HashMap<String, Member>>,
+    ) -> Vec<Relocatable> {
+        let vm = self.vm.borrow();
+        hint_data_dictionary
+            .get(&vm.get_pc().offset)
+            .into_iter()
+            .flatten()
+            .filter_map(|hint_data| hint_data.downcast_ref::<HintProcessorData>())
+            .flat_map(|hint_data| {
+                ids_cells(
+                    &hint_data.ids_data,
+                    &vm,
+                    &hint_data.ap_tracking,
+                    struct_types,
+                )
+            })
+            .collect()
+    }
+
+    /// Calls `func(vm, ids, memory, scopes)` to run a hint registered with
+    /// `CairoRunner.register_hint`. `scopes` is a dict with the variables of the current scope,
+    /// which are updated with the changes made to it, deletions included.
+    fn execute_registered_hint(
+        &self,
+        func: &PyObject,
+        hint_data: &HintProcessorData,
+        exec_scopes: &mut ExecutionScopes,
+        constants: &HashMap<String, BigInt>,
+        struct_types: Rc<HashMap<String, HashMap<String, Member>>>,
     ) -> Result<(), PyErr> {
-        self.step_hint(
-            hint_executor,
-            hint_locals,
-            exec_scopes,
-            hint_data_dictionary,
-            struct_types,
-            constants,
-            static_locals,
+        Python::with_gil(|py| -> Result<(), PyErr> {
+            let ids = PyIds::new(
+                self,
+                &hint_data.ids_data,
+                &hint_data.ap_tracking,
+                constants,
+                struct_types,
+            );
+            let scopes = PyDict::new(py);
+            add_scope_locals(scopes, exec_scopes)?;
+            let shared_names = scopes.keys().extract::<Vec<String>>()?;
+
+            func.call1(py, (self.share(), ids, PyMemory::new(self), scopes))?;
+
+            for name in shared_names {
+                if !scopes.contains(&name)? {
+                    if let Some(scope) = exec_scopes.data.last_mut() {
+                        scope.remove(&name);
+                    }
+                }
+            }
+            for (name, value) in scopes {
+                assign_scope_variable_from_py(exec_scopes, &name.to_string(), value);
+            }
+            Ok(())
+        })
+    }
+
+    /// Returns a namespace for the next hint of this VM: a shallow copy of a base namespace with
+    /// the entries that don't change between hints, which are only built once. Hints can't
+    /// change the base namespace, so names they define, delete or rebind don't reach later hints.
+    /// In sandbox mode, each hint also gets its own copy of the sandbox builtins.
+    fn hint_globals<'py>(&mut self, py: Python<'py>) -> Result<&'py PyDict, PyErr> {
+        let globals = match &self.hint_globals {
+            Some(globals) => globals.as_ref(py).copy()?,
+            None => {
+                let globals = self.base_hint_globals(py)?;
+                self.hint_globals = Some(globals.into());
+                globals.copy()?
+            }
+        };
+        if let Some(sandbox) = &mut self.sandbox {
+            globals.set_item("__builtins__", sandbox.builtins(py)?)?;
+        }
+        Ok(globals)
+    }
+
+    fn base_hint_globals<'py>(&self, py: Python<'py>) -> Result<&'py PyDict, PyErr> {
+        let memory = PyMemory::new(self);
+        let segments = PySegmentManager::new(self, memory.clone());
+        let range_check_builtin = PyRangeCheck::from((*self.vm).borrow().get_range_check_builtin());
+        let prime = (*self.vm).borrow().get_prime().clone();
+
+        let globals = PyDict::new(py);
+        if self.sandbox.is_none() {
+            globals.set_item("__builtins__", py.import("builtins")?)?;
+        }
+        globals.set_item("memory", pycell!(py, memory))?;
+        globals.set_item("segments", pycell!(py, segments))?;
+        globals.set_item("range_check_builtin", range_check_builtin)?;
+        globals.set_item("PRIME", prime)?;
+        globals.set_item(
+            "to_felt_or_relocatable",
+            pycell!(py, ToFeltOrRelocatableFunc),
         )?;
-        self.vm.borrow_mut().step_instruction().map_err(to_py_error)
+        Ok(globals)
+    }
+
+    /// Returns the code object of the `hint_index`-th hint at the current pc, compiling `code`
+    /// only if it wasn't run before by this VM or by any other runner in the process.
+    fn compiled_hint_code(
+        &mut self,
+        py: Python,
+        hint_index: usize,
+        code: &str,
+    ) -> Result<PyObject, PyErr> {
+        let key = ((*self.vm).borrow().get_pc().offset, hint_index);
+        if let Some((cached_code, code_object)) = self.compiled_hints.get(&key) {
+            if cached_code == code {
+                return Ok(code_object.clone_ref(py));
+            }
+        }
+        let code_object = compile_hint_code(py, code)?;
+        self.compiled_hints
+            .insert(key, (code.to_string(), code_object.clone_ref(py)));
+        Ok(code_object)
     }
 
     fn should_run_py_hint(
@@ -236,6 +500,12 @@ impl PyVM {
         hint_data: &Box<dyn Any>,
         constants: &HashMap<String, BigInt>,
     ) -> Result<bool, VirtualMachineError> {
+        // Registered hints take precedence over the builtin implementations
+        if let Some(hint_data) = hint_data.downcast_ref::<HintProcessorData>() {
+            if self.registered_hints.contains_key(&hint_data.code) {
+                return Ok(true);
+            }
+        }
         let mut vm = self.vm.borrow_mut();
         match hint_executor.execute_hint(&mut vm, exec_scopes, hint_data, constants) {
             Ok(()) => Ok(false),
@@ -245,12 +515,34 @@ impl PyVM {
     }
 }
 
+/// Compiles the source of a hint, reusing the code object of any previous compilation of the
+/// same source. The lock isn't held while compiling, as Python may switch threads meanwhile.
+fn compile_hint_code(py: Python, code: &str) -> Result<PyObject, PyErr> {
+    if let Some(code_object) = compiled_hint_codes().get(py, code) {
+        return Ok(code_object);
+    }
+    let code_object: PyObject = py
+        .import("builtins")?
+        .getattr("compile")?
+        .call1((code, "<string>", "exec"))?
+        .into();
+    compiled_hint_codes().insert(code.to_string(), code_object.clone_ref(py));
+    Ok(code_object)
+}
+
+fn compiled_hint_codes() -> MutexGuard<'static, HintCodeCache> {
+    COMPILED_HINT_CODES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
 pub(crate) fn add_scope_locals(
     globals: &PyDict,
     exec_scopes: &ExecutionScopes,
 ) -> Result<(), PyErr> {
+    let py = globals.py();
     for (name, elem) in exec_scopes.get_local_variables().map_err(to_py_error)? {
-        if let Some(pyobj) = elem.downcast_ref::<PyObject>() {
+        if let Some(pyobj) = scope_variable_to_py(py, elem.as_ref()) {
             globals.set_item(name, pyobj)?;
         }
     }
@@ -273,7 +565,7 @@ pub(crate) fn update_scope_hint_locals(
             if hint_locals.keys().cloned().any(|x| x == name) {
                 hint_locals.insert(name, elem.to_object(py));
             } else {
-                exec_scopes.assign_or_update_variable(&name, any_box!(elem.to_object(py)));
+                assign_scope_variable_from_py(exec_scopes, &name, elem);
             }
         }
     }
@@ -281,9 +573,12 @@ pub(crate) fn update_scope_hint_locals(
 
 #[cfg(test)]
 mod test {
-    use crate::{relocatable::PyMaybeRelocatable, vm_core::PyVM};
+    use crate::{
+        sandbox::{HintSandboxError, Sandbox},
+        vm_core::{compile_hint_code, HintCodeCache, PyVM},
+    };
     use cairo_rs::{
-        bigint,
+        any_box, bigint,
         hint_processor::{
             builtin_hint_processor::builtin_hint_processor_definition::{
                 BuiltinHintProcessor, HintProcessorData,
@@ -311,6 +606,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut ExecutionScopes::new(),
                 &HashMap::new(),
@@ -332,6 +628,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut ExecutionScopes::new(),
                 &HashMap::new(),
@@ -341,6 +638,341 @@ mod test {
             .is_ok());
     }
 
+    #[test]
+    fn compiled_hints_are_reused() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let execute_hint = |vm: &mut PyVM, code: &str| {
+            let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+            vm.execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut ExecutionScopes::new(),
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+        };
+
+        execute_hint(&mut vm, "x = 1").unwrap();
+        let code_object = vm.compiled_hints[&(0, 0)].1.clone();
+        execute_hint(&mut vm, "x = 1").unwrap();
+        assert_eq!(vm.compiled_hints.len(), 1);
+        Python::with_gil(|py| {
+            assert!(vm.compiled_hints[&(0, 0)].1.is(&code_object));
+            assert!(compile_hint_code(py, "x = 1").unwrap().is(&code_object));
+        });
+
+        // A different hint at the same pc and index is compiled again
+        execute_hint(&mut vm, "x = 2").unwrap();
+        assert_eq!(vm.compiled_hints[&(0, 0)].0, "x = 2");
+        assert!(execute_hint(&mut vm, "x = (").is_err());
+    }
+
+    #[test]
+    fn hint_code_cache_drops_least_recently_used() {
+        Python::with_gil(|py| {
+            let mut cache = HintCodeCache::new(2);
+            cache.insert(String::from("x = 1"), 1.to_object(py));
+            cache.insert(String::from("x = 2"), 2.to_object(py));
+            assert!(cache.get(py, "x = 1").is_some());
+
+            cache.insert(String::from("x = 3"), 3.to_object(py));
+            assert_eq!(cache.code_objects.len(), 2);
+            assert!(cache.get(py, "x = 2").is_none());
+            assert!(cache.get(py, "x = 1").is_some());
+            assert!(cache.get(py, "x = 3").is_some());
+
+            // Inserting a cached source again doesn't drop anything
+            cache.insert(String::from("x = 1"), 1.to_object(py));
+            assert_eq!(cache.code_objects.len(), 2);
+            assert_eq!(cache.uses.len(), 2);
+            cache.insert(String::from("x = 4"), 4.to_object(py));
+            assert!(cache.get(py, "x = 3").is_none());
+            assert!(cache.get(py, "x = 1").is_some());
+        });
+    }
+
+    #[test]
+    fn hint_globals_are_reused() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let mut execute_hint = |code: &str, exec_scopes: &mut ExecutionScopes| {
+            let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+            vm.execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                exec_scopes,
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+        };
+        Python::with_gil(|py| {
+            py.import("__main__")
+                .unwrap()
+                .setattr("main_variable", 1)
+                .unwrap()
+        });
+
+        let mut exec_scopes = ExecutionScopes::new();
+        execute_hint("first_memory = id(memory); x = 1", &mut exec_scopes).unwrap();
+        execute_hint(
+            "assert id(memory) == first_memory; assert x == 1",
+            &mut exec_scopes,
+        )
+        .unwrap();
+        // Names left by previous hints, or defined in __main__, aren't visible
+        let result = execute_hint(
+            "assert 'x' not in globals() and 'main_variable' not in globals()",
+            &mut ExecutionScopes::new(),
+        );
+        Python::with_gil(|py| {
+            py.import("__main__")
+                .unwrap()
+                .delattr("main_variable")
+                .unwrap()
+        });
+        result.unwrap();
+
+        // Rebinding or deleting the shared entries only affects the hint doing it
+        execute_hint(
+            "memory = None; del segments; __builtins__ = {}",
+            &mut ExecutionScopes::new(),
+        )
+        .unwrap();
+        execute_hint(
+            "assert id(memory) == first_memory; segments; len([])",
+            &mut exec_scopes,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn sandboxed_hints() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let execute_hint = |vm: &mut PyVM, code: &str| {
+            let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+            vm.execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut ExecutionScopes::new(),
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+        };
+
+        vm.set_sandbox(Some(Sandbox::new(None)));
+        assert!(execute_hint(&mut vm, "import math; print(ap)").is_ok());
+        Python::with_gil(|py| {
+            for code in ["import os", "len.__self__.__import__('os')"] {
+                assert!(execute_hint(&mut vm, code)
+                    .unwrap_err()
+                    .is_instance_of::<HintSandboxError>(py))
+            }
+        });
+        // Changes to the builtins don't reach the next hints
+        assert!(execute_hint(&mut vm, "__builtins__['len'] = None; __builtins__ = {}").is_ok());
+        assert!(execute_hint(&mut vm, "assert len([]) == 0").is_ok());
+
+        vm.set_sandbox(None);
+        assert!(execute_hint(&mut vm, "import os").is_ok());
+    }
+
+    #[test]
+    fn dict_manager_hint() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let code = "dict_ptr = __dict_manager.new_dict(segments, {1: 2})";
+        let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+        let mut exec_scopes = ExecutionScopes::new();
+        assert!(vm
+            .execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut exec_scopes,
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+            .is_ok());
+        assert_eq!(
+            exec_scopes
+                .get_dict_manager()
+                .unwrap()
+                .borrow()
+                .trackers
+                .len(),
+            1
+        );
+
+        let code = "assert __dict_manager.get_tracker(dict_ptr).data[1] == 2";
+        let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+        assert!(vm
+            .execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut exec_scopes,
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn dict_squash_hint() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        // As cairo-lang's dict_squash, copies the dictionary to a new one
+        let code = r#"
+dict_ptr = __dict_manager.new_default_dict(segments, 7, {1: 2, 3: 4})
+data = __dict_manager.get_tracker(dict_ptr).data
+initial_dict = {key: data.get(key) for key in data}
+assert initial_dict == {1: 2, 3: 4}
+assert sorted(data.items()) == [(1, 2), (3, 4)]
+assert 5 not in data and data.get(5) is None and data.get(5, 0) == 0 and len(data) == 2
+squashed_dict_ptr = __dict_manager.new_dict(segments, dict(data.items()))
+assert __dict_manager.get_dict(squashed_dict_ptr) == initial_dict
+"#;
+        let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+        assert!(vm
+            .execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut ExecutionScopes::new(),
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn cast_hint() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let struct_types = HashMap::from([(
+            String::from("__main__.Point"),
+            HashMap::from([
+                (
+                    String::from("x"),
+                    Member {
+                        cairo_type: String::from("felt"),
+                        offset: 0,
+                    },
+                ),
+                (
+                    String::from("y"),
+                    Member {
+                        cairo_type: String::from("felt"),
+                        offset: 1,
+                    },
+                ),
+            ]),
+        )]);
+        let code = r#"
+p = segments.add()
+cast(p, "Point*").y = 7
+points = cast(p, "__main__.Point*")
+points[1] = (8, 9)
+assert points[0].y == 7
+assert cast(p, "Point").address_ == p
+assert memory[p + 1] == 7
+assert memory[p + 3] == 9
+"#;
+        let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+        assert!(vm
+            .execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut ExecutionScopes::new(),
+                &HashMap::new(),
+                Rc::new(struct_types),
+                None,
+            )
+            .is_ok());
+
+        let code = "cast(segments.add(), \"felt*\")";
+        let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+        assert!(vm
+            .execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut ExecutionScopes::new(),
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn rust_scope_variables_hint() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let mut exec_scopes = ExecutionScopes::new();
+        exec_scopes.assign_or_update_variable("n", any_box!(bigint!(3)));
+        exec_scopes
+            .assign_or_update_variable("keys", any_box!(vec![MaybeRelocatable::from(bigint!(1))]));
+        let code = "n -= 1\nkeys.append(2)";
+        let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
+        assert!(vm
+            .execute_hint(
+                &hint_data,
+                0,
+                &mut HashMap::new(),
+                &mut exec_scopes,
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+                None,
+            )
+            .is_ok());
+
+        assert_eq!(
+            exec_scopes.data[0]["n"].downcast_ref::<BigInt>(),
+            Some(&bigint!(2))
+        );
+        assert_eq!(
+            exec_scopes.data[0]["keys"].downcast_ref::<Vec<MaybeRelocatable>>(),
+            Some(&vec![
+                MaybeRelocatable::from(bigint!(1)),
+                MaybeRelocatable::from(bigint!(2))
+            ])
+        );
+    }
+
     #[test]
     fn ids_hint() {
         let mut vm = PyVM::new(
@@ -367,6 +999,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut ExecutionScopes::new(),
                 &HashMap::new(),
@@ -398,6 +1031,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &constants,
@@ -412,6 +1046,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &constants,
@@ -454,7 +1089,7 @@ mod test {
             .unwrap();
 
         assert!(vm
-            .step(
+            .run_hints(
                 &mut hint_processor,
                 &mut HashMap::new(),
                 &mut ExecutionScopes::new(),
@@ -464,6 +1099,7 @@ mod test {
                 None,
             )
             .is_ok());
+        assert!(vm.run_instruction().is_ok());
     }
 
     #[test]
@@ -504,7 +1140,7 @@ mod test {
         hint_data.insert(0, hint_proc_data);
 
         assert!(vm
-            .step(
+            .run_hints(
                 &mut hint_processor,
                 &mut HashMap::new(),
                 &mut ExecutionScopes::new(),
@@ -514,6 +1150,7 @@ mod test {
                 None,
             )
             .is_ok());
+        assert!(vm.run_instruction().is_ok());
     }
 
     #[test]
@@ -535,6 +1172,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -546,6 +1184,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -575,6 +1214,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -586,6 +1226,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -597,6 +1238,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -608,6 +1250,7 @@ mod test {
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -632,6 +1275,7 @@ print(word)";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut hint_locals,
                 &mut ExecutionScopes::new(),
                 &HashMap::new(),
@@ -661,6 +1305,7 @@ print(word)";
         let hint_data = HintProcessorData::new_default(code.to_string(), HashMap::new());
         let x = vm.execute_hint(
             &hint_data,
+            0,
             &mut HashMap::new(),
             &mut exec_scopes,
             &HashMap::new(),
@@ -683,6 +1328,7 @@ print(word)";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -707,6 +1353,7 @@ vm_exit_scope()";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -731,6 +1378,7 @@ vm_exit_scope()";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -743,6 +1391,7 @@ vm_exit_scope()";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -768,6 +1417,7 @@ vm_enter_scope()";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -792,6 +1442,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -815,6 +1466,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -826,6 +1478,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -850,6 +1503,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -872,6 +1526,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -879,18 +1534,14 @@ lista_b = [lista_a[k] for k in range(2)]";
                 None,
             )
             .is_ok());
-        Python::with_gil(|py| {
-            assert_eq!(
-                exec_scopes
-                    .get_any_boxed_ref("felt")
-                    .unwrap()
-                    .downcast_ref::<PyObject>()
-                    .unwrap()
-                    .extract::<PyMaybeRelocatable>(py)
-                    .unwrap(),
-                PyMaybeRelocatable::from(bigint!(456))
-            );
-        });
+        // Ints are stored as BigInts, so that builtin hints can read them
+        assert_eq!(
+            exec_scopes
+                .get_any_boxed_ref("felt")
+                .unwrap()
+                .downcast_ref::<BigInt>(),
+            Some(&bigint!(456))
+        );
     }
 
     #[test]
@@ -906,6 +1557,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -939,6 +1591,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -996,6 +1649,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(pyvm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -1039,6 +1693,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(pyvm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut ExecutionScopes::new(),
                 &HashMap::new(),
@@ -1065,6 +1720,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -1072,16 +1728,13 @@ lista_b = [lista_a[k] for k in range(2)]";
                 Some(&static_locals),
             )
             .is_ok());
-        let number = Python::with_gil(|py| -> usize {
+        assert_eq!(
             exec_scopes.data[0]
                 .get("number")
                 .unwrap()
-                .downcast_ref::<PyObject>()
-                .unwrap()
-                .extract::<usize>(py)
-                .unwrap()
-        });
-        assert_eq!(number, 90)
+                .downcast_ref::<BigInt>(),
+            Some(&bigint!(90))
+        );
     }
 
     #[test]
@@ -1101,6 +1754,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut HashMap::new(),
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -1136,6 +1790,7 @@ lista_b = [lista_a[k] for k in range(2)]";
         assert!(vm
             .execute_hint(
                 &hint_data,
+                0,
                 &mut hint_locals,
                 &mut exec_scopes,
                 &HashMap::new(),
@@ -1166,6 +1821,95 @@ lista_b = [lista_a[k] for k in range(2)]";
             .is_err());
     }
 
+    #[test]
+    fn registered_hint_deletes_scope_variables() {
+        let vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        let mut exec_scopes = ExecutionScopes::new();
+        exec_scopes.assign_or_update_variable("n", any_box!(bigint!(3)));
+        exec_scopes.assign_or_update_variable("m", any_box!(bigint!(4)));
+        let hint_data = HintProcessorData::new_default(String::from("del n"), HashMap::new());
+        Python::with_gil(|py| {
+            let func = py
+                .eval(
+                    "lambda vm, ids, memory, scopes: scopes.pop('n')",
+                    None,
+                    None,
+                )
+                .unwrap()
+                .to_object(py);
+            vm.execute_registered_hint(
+                &func,
+                &hint_data,
+                &mut exec_scopes,
+                &HashMap::new(),
+                Rc::new(HashMap::new()),
+            )
+            .unwrap();
+        });
+
+        let variables = exec_scopes.get_local_variables().unwrap();
+        assert!(!variables.contains_key("n"));
+        assert_eq!(variables["m"].downcast_ref::<BigInt>(), Some(&bigint!(4)));
+    }
+
+    #[test]
+    fn registered_hint_replaces_builtin_hint() {
+        let mut vm = PyVM::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        for _ in 0..2 {
+            vm.vm.borrow_mut().add_memory_segment();
+        }
+        let code = "memory[ap] = segments.add()";
+        Python::with_gil(|py| {
+            let func = py
+                .eval(
+                    "lambda vm, ids, memory, scopes: (memory.__setitem__(vm.run_context.ap, 7), scopes.__setitem__('called', True))",
+                    None,
+                    None,
+                )
+                .unwrap();
+            vm.registered_hints
+                .insert(code.to_string(), func.to_object(py));
+        });
+        let hint_data_dictionary = HashMap::from([(
+            0,
+            vec![any_box!(HintProcessorData::new_default(
+                code.to_string(),
+                HashMap::new()
+            ))],
+        )]);
+        let mut exec_scopes = ExecutionScopes::new();
+
+        assert_eq!(
+            vm.step_hint(
+                &mut BuiltinHintProcessor::new_empty(),
+                &mut HashMap::new(),
+                &mut exec_scopes,
+                &hint_data_dictionary,
+                Rc::new(HashMap::new()),
+                &HashMap::new(),
+                None,
+            )
+            .unwrap(),
+            1
+        );
+        assert_eq!(
+            vm.vm.borrow().get_maybe(&Relocatable::from((1, 0))),
+            Ok(Some(MaybeRelocatable::from(bigint!(7))))
+        );
+        assert!(exec_scopes
+            .get_local_variables()
+            .unwrap()
+            .contains_key("called"));
+    }
+
     #[test]
     fn run_context() {
         let vm = PyVM::new(
@@ -0,0 +1,383 @@
+use crate::relocatable::{PyMaybeRelocatable, PyRelocatable};
+use cairo_rs::{
+    types::{
+        instruction::{Instruction, Op1Addr, Register},
+        relocatable::{MaybeRelocatable, Relocatable},
+    },
+    vm::{decoding::decoder::decode_instruction, vm_core::VirtualMachine},
+};
+use pyo3::{exceptions::PyTypeError, prelude::*};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+const WATCH_TYPE_ERROR_MSG: &str =
+    "watched memory must be a RelocatableValue or a (RelocatableValue, size) pair";
+
+/// The memory accesses of a run, kept once `CairoRunner.enable_access_log` is called.
+pub(crate) type AccessLog = Rc<RefCell<Option<Vec<PyMemoryAccess>>>>;
+
+/// A read or write of a memory cell by the instruction at `pc`, or a write by one of its hints,
+/// as given to watchpoint callbacks and kept in the access log.
+#[pyclass(name = "MemoryAccess")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PyMemoryAccess {
+    /// "read" or "write".
+    #[pyo3(get)]
+    kind: &'static str,
+    pub(crate) address: Relocatable,
+    value: Option<MaybeRelocatable>,
+    pc: Relocatable,
+    /// Number of steps run before the access.
+    #[pyo3(get)]
+    step: usize,
+}
+
+#[pymethods]
+impl PyMemoryAccess {
+    #[getter]
+    fn address(&self) -> PyRelocatable {
+        PyRelocatable::from(self.address)
+    }
+
+    #[getter]
+    fn value(&self, py: Python) -> PyObject {
+        match &self.value {
+            Some(value) => PyMaybeRelocatable::from(value).to_object(py),
+            None => py.None(),
+        }
+    }
+
+    #[getter]
+    fn pc(&self) -> PyRelocatable {
+        PyRelocatable::from(self.pc)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "MemoryAccess({} {} at step {}, pc {})",
+            self.kind,
+            PyRelocatable::from(self.address).__repr__(),
+            self.step,
+            PyRelocatable::from(self.pc).__repr__()
+        )
+    }
+}
+
+struct Watchpoint {
+    id: usize,
+    start: Relocatable,
+    size: usize,
+    callback: PyObject,
+    reads: bool,
+}
+
+impl Watchpoint {
+    fn contains(&self, address: &Relocatable) -> bool {
+        address.segment_index == self.start.segment_index
+            && address.offset >= self.start.offset
+            && address.offset < self.start.offset + self.size
+    }
+
+    fn cells(&self) -> impl Iterator<Item = Relocatable> + '_ {
+        (0..self.size).map(|offset| self.start + offset)
+    }
+}
+
+/// What a step accessed, gathered as it runs: the hints first, then the instruction.
+pub(crate) struct StepAccesses {
+    pc: Relocatable,
+    step: usize,
+    /// Number of cells of each segment before the hints ran.
+    segment_sizes: Vec<usize>,
+    /// Cells that weren't known before the hints ran and that they may write without growing a
+    /// segment: the watched ones and those of the ids of the hints.
+    unknown_cells: Vec<Relocatable>,
+    /// Cells written by the hints.
+    hint_writes: Vec<Relocatable>,
+    /// The instruction, once the hints ran.
+    instruction: Option<InstructionAccesses>,
+}
+
+/// The instruction of a step, along with the registers it runs with and whether its operands
+/// were known before it ran.
+struct InstructionAccesses {
+    instruction: Instruction,
+    ap: Relocatable,
+    fp: Relocatable,
+    /// Whether dst, op0 and op1 were known, or None when the address of op1 depends on an op0
+    /// that the instruction deduces.
+    known: [Option<bool>; 3],
+}
+
+impl StepAccesses {
+    /// Records the writes of the hints, which just ran, and the state of the instruction's
+    /// operands before it runs.
+    pub(crate) fn after_hints(&mut self, vm: &VirtualMachine) {
+        let known = |address: &Relocatable| matches!(vm.get_maybe(address), Ok(Some(_)));
+        self.hint_writes = self.unknown_cells.iter().copied().filter(known).collect();
+        for (index, segment) in vm.memory.data.iter().enumerate() {
+            let old_size = self.segment_sizes.get(index).copied().unwrap_or(0);
+            for (offset, cell) in segment.iter().enumerate().skip(old_size) {
+                let address = Relocatable::from((index as isize, offset));
+                if cell.is_some() && !self.hint_writes.contains(&address) {
+                    self.hint_writes.push(address);
+                }
+            }
+        }
+        self.instruction = decode_current_instruction(vm).map(|instruction| {
+            let (ap, fp) = (vm.get_ap(), vm.get_fp());
+            let cells = operand_cells(vm, &instruction, self.pc, ap, fp);
+            InstructionAccesses {
+                known: cells.map(|cell| cell.map(|address| known(&address))),
+                instruction,
+                ap,
+                fp,
+            }
+        });
+    }
+}
+
+/// The watchpoints set with `CairoRunner.watch`.
+#[derive(Default)]
+pub(crate) struct Watchpoints {
+    watchpoints: Vec<Watchpoint>,
+    next_id: usize,
+    /// Watched cells that may not be known yet. As cells can't be unset, it only shrinks as they
+    /// get written, so that a step doesn't go through all the watched cells.
+    unknown_cells: HashSet<Relocatable>,
+}
+
+impl Watchpoints {
+    /// Watches a cell, given as a RelocatableValue, or `size` cells from `start`, given as a
+    /// `(start, size)` pair, returning the id of the watchpoint.
+    pub(crate) fn add(
+        &mut self,
+        vm: &VirtualMachine,
+        memory: &PyAny,
+        callback: PyObject,
+        reads: bool,
+    ) -> PyResult<usize> {
+        let (start, size) = if let Ok(start) = memory.extract::<PyRelocatable>() {
+            (Relocatable::from(&start), 1)
+        } else if let Ok((start, size)) = memory.extract::<(PyRelocatable, usize)>() {
+            (Relocatable::from(&start), size)
+        } else {
+            return Err(PyTypeError::new_err(WATCH_TYPE_ERROR_MSG));
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let watchpoint = Watchpoint {
+            id,
+            start,
+            size,
+            callback,
+            reads,
+        };
+        self.unknown_cells.extend(
+            watchpoint
+                .cells()
+                .filter(|address| !matches!(vm.get_maybe(address), Ok(Some(_)))),
+        );
+        self.watchpoints.push(watchpoint);
+        Ok(id)
+    }
+
+    pub(crate) fn remove(&mut self, id: usize) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        let watchpoints = &self.watchpoints;
+        self.unknown_cells.retain(|address| {
+            watchpoints
+                .iter()
+                .any(|watchpoint| watchpoint.contains(address))
+        });
+        self.watchpoints.len() != len
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.watchpoints.clear();
+        self.unknown_cells.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+
+    /// Starts gathering the accesses of a step, before its hints run. `hint_cells` are the cells
+    /// of the ids of the hints, which they may write.
+    pub(crate) fn before_step(
+        &self,
+        vm: &VirtualMachine,
+        hint_cells: impl IntoIterator<Item = Relocatable>,
+    ) -> StepAccesses {
+        let known = |address: &Relocatable| matches!(vm.get_maybe(address), Ok(Some(_)));
+        let mut unknown_cells = self
+            .unknown_cells
+            .iter()
+            .copied()
+            .chain(hint_cells)
+            .filter(|address| !known(address))
+            .collect::<Vec<_>>();
+        unknown_cells.sort_unstable_by_key(|address| (address.segment_index, address.offset));
+        unknown_cells.dedup();
+        StepAccesses {
+            pc: *vm.get_pc(),
+            step: vm.current_step,
+            segment_sizes: vm.memory.data.iter().map(Vec::len).collect(),
+            unknown_cells,
+            hint_writes: Vec::new(),
+            instruction: None,
+        }
+    }
+
+    /// Reports the accesses of the step that just ran to the watchpoints and the access log.
+    pub(crate) fn after_step(
+        &mut self,
+        vm: &RefCell<VirtualMachine>,
+        before: StepAccesses,
+        access_log: &AccessLog,
+    ) -> PyResult<()> {
+        let accesses = {
+            let vm = vm.borrow();
+            let access = |kind, address: Relocatable| PyMemoryAccess {
+                kind,
+                address,
+                value: vm.get_maybe(&address).ok().flatten(),
+                pc: before.pc,
+                step: before.step,
+            };
+            let mut accesses = before
+                .hint_writes
+                .iter()
+                .map(|address| access("write", *address))
+                .collect::<Vec<_>>();
+            // The operands are computed once the instruction ran, as op1 may be read from an op0
+            // that it deduced
+            if let Some(instruction) = &before.instruction {
+                let cells = operand_cells(
+                    &vm,
+                    &instruction.instruction,
+                    before.pc,
+                    instruction.ap,
+                    instruction.fp,
+                );
+                let mut seen = Vec::new();
+                for (cell, known) in cells.into_iter().zip(instruction.known) {
+                    let address = match cell {
+                        Some(address) if !seen.contains(&address) => address,
+                        _ => continue,
+                    };
+                    seen.push(address);
+                    let kind = if known == Some(false) {
+                        "write"
+                    } else {
+                        "read"
+                    };
+                    accesses.push(access(kind, address));
+                }
+            }
+            self.unknown_cells
+                .retain(|address| !matches!(vm.get_maybe(address), Ok(Some(_))));
+            accesses
+        };
+
+        if let Some(log) = access_log.borrow_mut().as_mut() {
+            log.extend(accesses.iter().cloned());
+        }
+        Python::with_gil(|py| {
+            for access in accesses {
+                for watchpoint in self.watchpoints.iter() {
+                    if watchpoint.contains(&access.address)
+                        && (access.kind == "write" || watchpoint.reads)
+                    {
+                        watchpoint.callback.call1(py, (access.clone(),))?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Decodes the instruction at pc, if it's known.
+fn decode_current_instruction(vm: &VirtualMachine) -> Option<Instruction> {
+    let pc = *vm.get_pc();
+    let encoded = match vm.get_maybe(&pc) {
+        Ok(Some(MaybeRelocatable::Int(encoded))) => i64::try_from(&encoded).ok()?,
+        _ => return None,
+    };
+    let imm = match vm.get_maybe(&(pc + 1)) {
+        Ok(Some(MaybeRelocatable::Int(imm))) => Some(imm),
+        _ => None,
+    };
+    decode_instruction(encoded, imm).ok()
+}
+
+/// Returns the addresses of the dst, op0 and op1 of an instruction run at `pc` with the given
+/// registers. That of op1 is None when it's read from an op0 that isn't known.
+fn operand_cells(
+    vm: &VirtualMachine,
+    instruction: &Instruction,
+    pc: Relocatable,
+    ap: Relocatable,
+    fp: Relocatable,
+) -> [Option<Relocatable>; 3] {
+    let register = |register: &Register| match register {
+        Register::AP => ap,
+        Register::FP => fp,
+    };
+    let dst = add_offset(register(&instruction.dst_register), instruction.off0);
+    let op0 = add_offset(register(&instruction.op0_register), instruction.off1);
+    let op1 = match instruction.op1_addr {
+        Op1Addr::Imm => add_offset(pc, instruction.off2),
+        Op1Addr::AP => add_offset(ap, instruction.off2),
+        Op1Addr::FP => add_offset(fp, instruction.off2),
+        Op1Addr::Op0 => op0.and_then(|op0| match vm.get_maybe(&op0) {
+            Ok(Some(MaybeRelocatable::RelocatableValue(base))) => {
+                add_offset(base, instruction.off2)
+            }
+            _ => None,
+        }),
+    };
+    [dst, op0, op1]
+}
+
+fn add_offset(base: Relocatable, offset: isize) -> Option<Relocatable> {
+    let offset = usize::try_from(base.offset as isize + offset).ok()?;
+    Some(Relocatable::from((base.segment_index, offset)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use num_bigint::{BigInt, Sign};
+
+    #[test]
+    fn operand_cells_of_assert_eq() {
+        let mut vm = VirtualMachine::new(
+            BigInt::new(Sign::Plus, vec![1, 0, 0, 0, 0, 0, 17, 134217728]),
+            false,
+            Vec::new(),
+        );
+        for _ in 0..2 {
+            vm.add_memory_segment();
+        }
+        // [ap + 5] = [fp + 3] + [pc + 1], i.e. [ap + 5] = [fp + 3] + imm
+        let pc = *vm.get_pc();
+        vm.insert_value(
+            &pc,
+            &MaybeRelocatable::from(BigInt::from(0x4026800180038005u64)),
+        )
+        .unwrap();
+        vm.insert_value(&(pc + 1), &MaybeRelocatable::from(BigInt::from(7)))
+            .unwrap();
+        let instruction = decode_current_instruction(&vm).unwrap();
+        assert_eq!(
+            operand_cells(&vm, &instruction, pc, vm.get_ap(), vm.get_fp()),
+            [
+                add_offset(vm.get_ap(), 5),
+                add_offset(vm.get_fp(), 3),
+                add_offset(pc, 1),
+            ]
+        );
+    }
+}